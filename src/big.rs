@@ -0,0 +1,394 @@
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Formatter},
+    ops::{Add, Mul},
+};
+
+// Arbitrary precision unsigned integer, little-endian base 2^32 limbs without trailing zeros
+// (so zero is an empty vec)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    fn trim(mut self) -> Self {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        match self.limbs.as_slice() {
+            [] => Some(0),
+            [lo] => Some(*lo as u64),
+            [lo, hi] => Some((*hi as u64) << 32 | *lo as u64),
+            _ => None,
+        }
+    }
+
+    fn bits(&self) -> usize {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() * 32 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    fn bit(&self, idx: usize) -> bool {
+        self.limbs
+            .get(idx / 32)
+            .is_some_and(|limb| limb >> (idx % 32) & 1 == 1)
+    }
+
+    fn shl1(&mut self) {
+        let mut carry = 0;
+        for limb in self.limbs.iter_mut() {
+            let next = *limb >> 31;
+            *limb = *limb << 1 | carry;
+            carry = next;
+        }
+        if carry != 0 {
+            self.limbs.push(carry);
+        }
+    }
+
+    pub fn checked_sub(&self, other: &BigUint) -> Option<BigUint> {
+        if *self < *other {
+            return None;
+        }
+
+        let mut limbs = Vec::with_capacity(self.limbs.len());
+        let mut borrow = 0i64;
+        for (idx, limb) in self.limbs.iter().enumerate() {
+            let mut diff = *limb as i64 - *other.limbs.get(idx).unwrap_or(&0) as i64 - borrow;
+            borrow = (diff < 0) as i64;
+            if diff < 0 {
+                diff += 1 << 32;
+            }
+            limbs.push(diff as u32);
+        }
+
+        Some(BigUint { limbs }.trim())
+    }
+
+    // Bit by bit long division, `None` when dividing by zero
+    pub fn checked_div_rem(&self, other: &BigUint) -> Option<(BigUint, BigUint)> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let mut quotient = vec![0u32; self.limbs.len()];
+        let mut rem = BigUint::zero();
+        for idx in (0..self.bits()).rev() {
+            rem.shl1();
+            if self.bit(idx) {
+                match rem.limbs.first_mut() {
+                    Some(lo) => *lo |= 1,
+                    None => rem.limbs.push(1),
+                }
+            }
+            if rem >= *other {
+                rem = rem.checked_sub(other).unwrap();
+                quotient[idx / 32] |= 1 << (idx % 32);
+            }
+        }
+
+        Some((BigUint { limbs: quotient }.trim(), rem))
+    }
+
+    pub fn pow(&self, mut exp: u32) -> BigUint {
+        let mut result = BigUint::from(1);
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = &result * &base;
+            }
+            base = &base * &base;
+            exp >>= 1;
+        }
+        result
+    }
+
+    // Like `pow`, but `None` instead of computing anything that could need more than
+    // `max_bits` bits
+    pub fn checked_pow(&self, exp: u64, max_bits: usize) -> Option<BigUint> {
+        // 0 and 1 never grow
+        if self.bits() <= 1 {
+            return Some(self.pow(exp.min(1) as u32));
+        }
+        if self.bits() as u128 * exp as u128 > max_bits as u128 {
+            return None;
+        }
+        Some(self.pow(exp as u32))
+    }
+
+    fn div_rem_small(&self, divisor: u32) -> (BigUint, u32) {
+        let mut limbs = vec![0u32; self.limbs.len()];
+        let mut rem = 0u64;
+        for idx in (0..self.limbs.len()).rev() {
+            let cur = rem << 32 | self.limbs[idx] as u64;
+            limbs[idx] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        (BigUint { limbs }.trim(), rem as u32)
+    }
+
+    // Number of decimal digits, zero counting as one
+    pub fn digits(&self) -> u32 {
+        self.to_string().len() as u32
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        BigUint {
+            limbs: vec![value as u32, (value >> 32) as u32],
+        }
+        .trim()
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint {
+        let len = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+        for idx in 0..len {
+            let sum = *self.limbs.get(idx).unwrap_or(&0) as u64
+                + *other.limbs.get(idx).unwrap_or(&0) as u64
+                + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        BigUint { limbs }.trim()
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+
+    fn add(self, other: BigUint) -> BigUint {
+        &self + &other
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint {
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.limbs.iter().enumerate() {
+                let cur = limbs[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        BigUint { limbs }.trim()
+    }
+}
+
+impl Mul for BigUint {
+    type Output = BigUint;
+
+    fn mul(self, other: BigUint) -> BigUint {
+        &self * &other
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // peel off 9 decimal digits at a time
+        let mut chunks = vec![];
+        let mut cur = self.clone();
+        while !cur.is_zero() {
+            let (next, rem) = cur.div_rem_small(1_000_000_000);
+            chunks.push(rem);
+            cur = next;
+        }
+
+        match chunks.split_last() {
+            None => f.pad("0"),
+            Some((first, rest)) => {
+                let mut s = first.to_string();
+                for chunk in rest.iter().rev() {
+                    s.push_str(&format!("{chunk:09}"));
+                }
+                f.pad(&s)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // Values around the limb boundaries plus random ones of every size up to u64
+    fn samples() -> Vec<u64> {
+        let mut samples = vec![
+            0,
+            1,
+            2,
+            9,
+            10,
+            999_999_999,
+            1_000_000_000,
+            u32::MAX as u64 - 1,
+            u32::MAX as u64,
+            u32::MAX as u64 + 1,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        let mut rng = Rng::new(35);
+        for bits in 1..=64 {
+            samples.push(rng.next_u64() >> (64 - bits));
+        }
+        samples
+    }
+
+    fn big(value: u128) -> BigUint {
+        &(&BigUint::from((value >> 64) as u64) * &BigUint::from(1 << 32).pow(2))
+            + &BigUint::from(value as u64)
+    }
+
+    #[test]
+    fn zero() {
+        assert!(BigUint::zero().is_zero());
+        assert!(BigUint::from(0).is_zero());
+        assert_eq!(BigUint::from(0), BigUint::zero());
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::zero().digits(), 1);
+        assert_eq!(BigUint::zero().to_u64(), Some(0));
+        assert_eq!(
+            BigUint::from(7).checked_sub(&BigUint::from(7)),
+            Some(BigUint::zero())
+        );
+        assert_eq!(BigUint::from(7).checked_div_rem(&BigUint::zero()), None);
+        assert_eq!(BigUint::zero().pow(0), BigUint::from(1));
+    }
+
+    #[test]
+    fn add_mul_sub_match_u128() {
+        let samples = samples();
+        for &a in &samples {
+            for &b in &samples {
+                let (x, y) = (BigUint::from(a), BigUint::from(b));
+                let (a, b) = (a as u128, b as u128);
+
+                assert_eq!(&x + &y, big(a + b), "{a} + {b}");
+                assert_eq!(&x * &y, big(a * b), "{a} * {b}");
+                assert_eq!(x.checked_sub(&y), a.checked_sub(b).map(big), "{a} - {b}");
+                assert_eq!(x.cmp(&y), a.cmp(&b), "{a} cmp {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn div_rem_matches_u128() {
+        let samples = samples();
+        // dividends up to 128 bits, carried across all four limbs
+        let dividends: Vec<u128> = samples
+            .iter()
+            .flat_map(|a| {
+                samples
+                    .iter()
+                    .step_by(4)
+                    .map(move |b| *a as u128 * *b as u128)
+            })
+            .flat_map(|n| [n, n.saturating_add(u64::MAX as u128)])
+            .collect();
+
+        for &a in &dividends {
+            let x = big(a);
+            for &d in samples.iter().step_by(3) {
+                let expected = (d != 0).then(|| (big(a / d as u128), big(a % d as u128)));
+                assert_eq!(x.checked_div_rem(&BigUint::from(d)), expected, "{a} / {d}");
+            }
+        }
+        // divisors wider than a limb
+        let x = big(u128::MAX);
+        let d = big(u64::MAX as u128 + 2);
+        assert_eq!(
+            x.checked_div_rem(&d),
+            Some((
+                big(u128::MAX / (u64::MAX as u128 + 2)),
+                big(u128::MAX % (u64::MAX as u128 + 2))
+            ))
+        );
+    }
+
+    #[test]
+    fn display_matches_u128() {
+        for &a in &samples() {
+            for &b in &samples() {
+                let value = a as u128 * b as u128;
+                let x = big(value);
+                assert_eq!(x.to_string(), value.to_string());
+                assert_eq!(x.digits(), value.to_string().len() as u32);
+                assert_eq!(x.to_u64(), u64::try_from(value).ok());
+            }
+        }
+        assert_eq!(format!("{:>5}", BigUint::from(42)), "   42");
+        assert_eq!(
+            BigUint::from(10).pow(30).to_string(),
+            format!("1{}", "0".repeat(30))
+        );
+    }
+
+    #[test]
+    fn checked_pow() {
+        for base in 0..20u64 {
+            for exp in 0..=(64 / (base.max(2).ilog2() + 1)) as u64 {
+                let expected = base.checked_pow(exp as u32).map(|n| big(n as u128));
+                assert_eq!(
+                    BigUint::from(base).checked_pow(exp, 64),
+                    expected,
+                    "{base}^{exp}"
+                );
+            }
+        }
+        assert_eq!(BigUint::from(2).checked_pow(40, 80), Some(big(1 << 40)));
+        assert_eq!(BigUint::from(2).checked_pow(40, 79), None);
+        assert_eq!(
+            BigUint::from(1).checked_pow(u64::MAX, 0),
+            Some(BigUint::from(1))
+        );
+        assert_eq!(
+            BigUint::from(0).checked_pow(u64::MAX, 0),
+            Some(BigUint::zero())
+        );
+        assert_eq!(BigUint::from(0).checked_pow(0, 0), Some(BigUint::from(1)));
+        assert_eq!(BigUint::from(u64::MAX).checked_pow(u64::MAX, 1 << 16), None);
+    }
+}
@@ -51,3 +51,157 @@ fn max_num(input: &str, max_len: usize) -> String {
     stack.truncate(max_len);
     stack.into_iter().collect()
 }
+
+// Like `max_num`, but picked batteries must be at least `min_gap` positions apart and
+// positions listed in `broken` can't be picked. Returns `None` if no valid pick exists.
+pub fn max_num_constrained(
+    input: &str,
+    max_len: usize,
+    min_gap: usize,
+    broken: &[usize],
+) -> Option<String> {
+    let digits = input.as_bytes();
+    let len = digits.len();
+    let gap = min_gap.max(1);
+
+    let mut usable = vec![true; len];
+    for &pos in broken {
+        if let Some(u) = usable.get_mut(pos) {
+            *u = false;
+        }
+    }
+
+    // can_pick[i][j]: j batteries can still be picked using only positions >= i
+    let mut can_pick = vec![vec![false; max_len + 1]; len + 1];
+    for row in can_pick.iter_mut() {
+        row[0] = true;
+    }
+    for i in (0..len).rev() {
+        for j in 1..=max_len {
+            let take = usable[i] && can_pick[(i + gap).min(len)][j - 1];
+            can_pick[i][j] = can_pick[i + 1][j] || take;
+        }
+    }
+
+    if !can_pick[0][max_len] {
+        return None;
+    }
+
+    let mut out = String::with_capacity(max_len);
+    let mut start = 0;
+    for left in (1..=max_len).rev() {
+        // the earliest occurrence of the biggest digit keeps the most options open
+        let mut best: Option<usize> = None;
+        for pos in start..len {
+            if !usable[pos] || !can_pick[(pos + gap).min(len)][left - 1] {
+                continue;
+            }
+            if best.is_none_or(|b| digits[pos] > digits[b]) {
+                best = Some(pos);
+            }
+        }
+        let pos = best?;
+        out.push(digits[pos] as char);
+        start = pos + gap;
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // Every pick of `max_len` positions, keeping the biggest number that fits the constraints
+    fn brute_force(
+        input: &str,
+        max_len: usize,
+        min_gap: usize,
+        broken: &[usize],
+    ) -> Option<String> {
+        let digits = input.as_bytes();
+        (0u32..1 << digits.len())
+            .filter(|mask| mask.count_ones() as usize == max_len)
+            .filter_map(|mask| {
+                let picked: Vec<usize> = (0..digits.len()).filter(|i| mask >> i & 1 == 1).collect();
+                let valid = picked.iter().all(|pos| !broken.contains(pos))
+                    && picked.windows(2).all(|w| w[1] - w[0] >= min_gap.max(1));
+                valid.then(|| {
+                    picked
+                        .iter()
+                        .map(|pos| digits[*pos] as char)
+                        .collect::<String>()
+                })
+            })
+            .max()
+    }
+
+    fn random_bank(rng: &mut Rng, len: usize) -> String {
+        (0..len)
+            .map(|_| char::from_digit(rng.range(1, 9) as u32, 10).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn unconstrained_matches_max_num() {
+        let mut rng = Rng::new(26);
+        for _ in 0..500 {
+            let len = rng.range(1, 40);
+            let bank = random_bank(&mut rng, len);
+            let max_len = rng.range(1, len);
+            let expected = max_num(&bank, max_len);
+            assert_eq!(
+                max_num_constrained(&bank, max_len, 0, &[]),
+                Some(expected.clone())
+            );
+            assert_eq!(max_num_constrained(&bank, max_len, 1, &[]), Some(expected));
+        }
+    }
+
+    #[test]
+    fn gaps() {
+        assert_eq!(
+            max_num_constrained("987654321", 2, 2, &[]).as_deref(),
+            Some("97")
+        );
+        assert_eq!(
+            max_num_constrained("9911", 2, 3, &[]).as_deref(),
+            Some("91")
+        );
+        assert_eq!(max_num_constrained("123", 2, 3, &[]), None);
+        assert_eq!(
+            max_num_constrained("818181911112111", 3, 4, &[]).as_deref(),
+            Some("911")
+        );
+    }
+
+    #[test]
+    fn broken_positions() {
+        assert_eq!(max_num_constrained("91", 1, 1, &[0]).as_deref(), Some("1"));
+        assert_eq!(
+            max_num_constrained("12345", 3, 1, &[3, 4]).as_deref(),
+            Some("123")
+        );
+        assert_eq!(max_num_constrained("12", 2, 1, &[1]), None);
+        // positions past the end are ignored
+        assert_eq!(max_num_constrained("12", 2, 1, &[5]).as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Rng::new(3);
+        for _ in 0..500 {
+            let len = rng.range(1, 12);
+            let bank = random_bank(&mut rng, len);
+            let max_len = rng.range(1, len.min(4));
+            let min_gap = rng.range(0, 3);
+            let broken: Vec<usize> = (0..len).filter(|_| rng.chance(0.2)).collect();
+            assert_eq!(
+                max_num_constrained(&bank, max_len, min_gap, &broken),
+                brute_force(&bank, max_len, min_gap, &broken),
+                "{bank} {max_len} {min_gap} {broken:?}"
+            );
+        }
+    }
+}
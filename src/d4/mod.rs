@@ -1,41 +1,315 @@
 use std::{
-    fs::File,
-    io::{BufRead, BufReader},
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
 };
 
-fn count_adjacent(x: usize, y: usize, input: &[Vec<bool>]) -> u8 {
-    let x_i32 = x as i32;
-    let y_i32 = y as i32;
-    let max_y = input.len() as i32;
-    let max_x = input[0].len() as i32;
-
-    let adjacent = vec![
-        (y_i32 - 1, x_i32 - 1),
-        (y_i32 - 1, x_i32),
-        (y_i32 - 1, x_i32 + 1),
-        (y_i32 + 1, x_i32 - 1),
-        (y_i32 + 1, x_i32),
-        (y_i32 + 1, x_i32 + 1),
-        (y_i32, x_i32 - 1),
-        (y_i32, x_i32 + 1),
-    ]
-    .into_iter()
-    .filter_map(|(y, x)| {
-        if !(0..max_y).contains(&y) || !(0..max_x).contains(&x) {
-            return None;
-        }
-        Some((y as usize, x as usize))
-    })
-    .collect::<Vec<_>>();
-
-    adjacent.iter().map(|(y, x)| input[*y][*x] as u8).sum()
+#[derive(Debug, Clone)]
+pub enum Neighbourhood {
+    // The 4 orthogonal cells
+    VonNeumann,
+    // The 8 surrounding cells
+    Moore,
+    // Every cell within `r` steps in any direction (Radius(1) == Moore)
+    Radius(u32),
+    // (dy, dx) offsets
+    Custom(Vec<(i32, i32)>),
 }
 
-pub fn solve() {
+impl Neighbourhood {
+    fn offsets(&self) -> Vec<(i32, i32)> {
+        match self {
+            Neighbourhood::VonNeumann => vec![(-1, 0), (1, 0), (0, -1), (0, 1)],
+            Neighbourhood::Moore => Neighbourhood::Radius(1).offsets(),
+            Neighbourhood::Radius(r) => {
+                let r = *r as i32;
+                (-r..=r)
+                    .flat_map(|dy| (-r..=r).map(move |dx| (dy, dx)))
+                    .filter(|offset| *offset != (0, 0))
+                    .collect()
+            }
+            Neighbourhood::Custom(offsets) => offsets.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+// A roll is accessible when its neighbour count compares to `value` with `cmp`
+#[derive(Debug, Clone, Copy)]
+pub struct Threshold {
+    pub cmp: Comparison,
+    pub value: u32,
+}
+
+impl Threshold {
+    fn accepts(&self, count: u32) -> bool {
+        match self.cmp {
+            Comparison::Less => count < self.value,
+            Comparison::LessOrEqual => count <= self.value,
+            Comparison::Equal => count == self.value,
+            Comparison::GreaterOrEqual => count >= self.value,
+            Comparison::Greater => count > self.value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edges {
+    // Cells outside the grid are empty
+    Bounded,
+    // The grid wraps around on both axes
+    Toroidal,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    offsets: Vec<(i32, i32)>,
+    threshold: Threshold,
+    edges: Edges,
+}
+
+impl Default for Rule {
+    // The puzzle rule: fewer than 4 rolls among the 8 surrounding cells
+    fn default() -> Self {
+        Self::new(
+            Neighbourhood::Moore,
+            Threshold {
+                cmp: Comparison::Less,
+                value: 4,
+            },
+            Edges::Bounded,
+        )
+    }
+}
+
+impl Rule {
+    pub fn new(neighbourhood: Neighbourhood, threshold: Threshold, edges: Edges) -> Self {
+        Self {
+            offsets: neighbourhood.offsets(),
+            threshold,
+            edges,
+        }
+    }
+
+    fn shift(
+        &self,
+        x: usize,
+        y: usize,
+        (dy, dx): (i32, i32),
+        input: &[Vec<bool>],
+    ) -> Option<(usize, usize)> {
+        let max_y = input.len() as i64;
+        let max_x = input[0].len() as i64;
+        let y = y as i64 + dy as i64;
+        let x = x as i64 + dx as i64;
+
+        match self.edges {
+            Edges::Bounded => {
+                if !(0..max_y).contains(&y) || !(0..max_x).contains(&x) {
+                    return None;
+                }
+                Some((y as usize, x as usize))
+            }
+            Edges::Toroidal => Some((y.rem_euclid(max_y) as usize, x.rem_euclid(max_x) as usize)),
+        }
+    }
+
+    // Cells whose rolls count towards (x, y)
+    fn neighbours<'a>(
+        &'a self,
+        x: usize,
+        y: usize,
+        input: &'a [Vec<bool>],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.offsets
+            .iter()
+            .filter_map(move |offset| self.shift(x, y, *offset, input))
+    }
+
+    // Cells that (x, y) counts towards, only differs from `neighbours` for asymmetric offsets
+    fn dependents<'a>(
+        &'a self,
+        x: usize,
+        y: usize,
+        input: &'a [Vec<bool>],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.offsets
+            .iter()
+            .filter_map(move |(dy, dx)| self.shift(x, y, (-dy, -dx), input))
+    }
+
+    fn count_adjacent(&self, x: usize, y: usize, input: &[Vec<bool>]) -> u32 {
+        self.neighbours(x, y, input)
+            .map(|(y, x)| input[y][x] as u32)
+            .sum()
+    }
+
+    fn is_accessible(&self, x: usize, y: usize, input: &[Vec<bool>]) -> bool {
+        input[y][x] && self.threshold.accepts(self.count_adjacent(x, y, input))
+    }
+}
+
+// Returns the wave in which each roll gets removed, starting at 1. Wave n contains every roll
+// that is accessible once waves 1..n have been removed, rolls that never go are `None`.
+// Neighbour counts are computed once, and after each wave only the cells that depend on a
+// removed roll are re-checked, since nothing else can have changed.
+pub fn removal_waves(input: &[Vec<bool>], rule: &Rule) -> Vec<Vec<Option<u32>>> {
+    let mut adjacent: Vec<Vec<u32>> = (0..input.len())
+        .map(|y| {
+            (0..input[y].len())
+                .map(|x| rule.count_adjacent(x, y, input))
+                .collect()
+        })
+        .collect();
+
+    let mut present = input.to_vec();
+    let mut waves: Vec<Vec<Option<u32>>> = input.iter().map(|row| vec![None; row.len()]).collect();
+    // last wave a cell was checked in, so it's only queued once per wave
+    let mut checked: Vec<Vec<u32>> = input.iter().map(|row| vec![0; row.len()]).collect();
+
+    let mut removing: Vec<(usize, usize)> = vec![];
+    for y in 0..input.len() {
+        for x in 0..input[y].len() {
+            if rule.is_accessible(x, y, input) {
+                removing.push((y, x));
+            }
+        }
+    }
+
+    let mut wave = 1;
+    while !removing.is_empty() {
+        for &(y, x) in removing.iter() {
+            present[y][x] = false;
+            waves[y][x] = Some(wave);
+        }
+        for &(y, x) in removing.iter() {
+            for (dy, dx) in rule.dependents(x, y, input) {
+                adjacent[dy][dx] -= 1;
+            }
+        }
+
+        let mut next = vec![];
+        for &(y, x) in removing.iter() {
+            for (dy, dx) in rule.dependents(x, y, input) {
+                if present[dy][dx]
+                    && checked[dy][dx] != wave
+                    && rule.threshold.accepts(adjacent[dy][dx])
+                {
+                    checked[dy][dx] = wave;
+                    next.push((dy, dx));
+                }
+            }
+        }
+
+        removing = next;
+        wave += 1;
+    }
+
+    waves
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WaveStyle {
+    // 1-9, then a-z, '+' for anything later
+    Number,
+    // Characters getting denser the later a roll goes
+    Gradient,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FrameFormat {
+    Text,
+    Ppm,
+}
+
+const GRADIENT: &[u8] = b".:-=+*#%";
+const PPM_SCALE: usize = 4;
+
+fn render_waves(input: &[Vec<bool>], waves: &[Vec<Option<u32>>], style: WaveStyle) -> String {
+    let last_wave = waves.iter().flatten().flatten().max().copied().unwrap_or(1);
+    let mut out = String::new();
+
+    for (y, row) in input.iter().enumerate() {
+        for (x, roll) in row.iter().enumerate() {
+            let c = match (roll, waves[y][x], style) {
+                (false, _, _) => ' ',
+                (true, None, _) => '@',
+                (true, Some(wave), WaveStyle::Number) => char::from_digit(wave, 36)
+                    .filter(|_| wave < 36)
+                    .unwrap_or('+'),
+                (true, Some(wave), WaveStyle::Gradient) => {
+                    let idx = (wave - 1) as usize * GRADIENT.len() / last_wave as usize;
+                    GRADIENT[idx] as char
+                }
+            };
+            out.push(c);
+        }
+        out.push('\n');
+    }
+
+    out
+}
+
+// Rolls still standing after `wave` waves have been removed
+fn rolls_after(input: &[Vec<bool>], waves: &[Vec<Option<u32>>], wave: u32) -> Vec<Vec<bool>> {
+    input
+        .iter()
+        .zip(waves)
+        .map(|(row, wave_row)| {
+            row.iter()
+                .zip(wave_row)
+                .map(|(roll, removed_in)| *roll && removed_in.is_none_or(|w| w > wave))
+                .collect()
+        })
+        .collect()
+}
+
+fn write_frame(path: &Path, rolls: &[Vec<bool>], format: FrameFormat) -> io::Result<()> {
+    let mut f = BufWriter::new(File::create(path)?);
+
+    match format {
+        FrameFormat::Text => {
+            for row in rolls {
+                let line: String = row.iter().map(|r| if *r { '@' } else { '.' }).collect();
+                writeln!(f, "{line}")?;
+            }
+        }
+        FrameFormat::Ppm => {
+            let height = rolls.len() * PPM_SCALE;
+            let width = rolls.first().map_or(0, |row| row.len()) * PPM_SCALE;
+            write!(f, "P6\n{width} {height}\n255\n")?;
+
+            for row in rolls {
+                let pixels: Vec<u8> = row
+                    .iter()
+                    .flat_map(|r| {
+                        let shade = if *r { 255 } else { 0 };
+                        [shade; 3 * PPM_SCALE]
+                    })
+                    .collect();
+                for _ in 0..PPM_SCALE {
+                    f.write_all(&pixels)?;
+                }
+            }
+        }
+    }
+
+    f.flush()
+}
+
+fn parse() -> Vec<Vec<bool>> {
     let f = File::open("./src/d4/input.txt").unwrap();
     let reader = BufReader::new(f);
 
-    let mut input: Vec<_> = reader
+    reader
         .lines()
         .map(|line| {
             line.unwrap()
@@ -43,16 +317,52 @@ pub fn solve() {
                 .map(|c| matches!(c, '@'))
                 .collect::<Vec<bool>>()
         })
-        .collect();
+        .collect()
+}
+
+// Prints which wave each roll is removed in, and optionally writes one frame per wave
+// (frame 0 being the untouched pile) into `frames`.
+pub fn solve_waves(
+    rule: &Rule,
+    style: WaveStyle,
+    frames: Option<(&Path, FrameFormat)>,
+) -> io::Result<()> {
+    let input = parse();
+    let waves = removal_waves(&input, rule);
+
+    print!("{}", render_waves(&input, &waves, style));
+
+    let last_wave = waves.iter().flatten().flatten().max().copied().unwrap_or(0);
+    println!("Waves: {last_wave}");
+
+    if let Some((dir, format)) = frames {
+        fs::create_dir_all(dir)?;
+        let ext = match format {
+            FrameFormat::Text => "txt",
+            FrameFormat::Ppm => "ppm",
+        };
+        for wave in 0..=last_wave {
+            let path = dir.join(format!("frame_{wave:04}.{ext}"));
+            write_frame(&path, &rolls_after(&input, &waves, wave), format)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn solve() {
+    let input = parse();
 
     dbg!(&input);
 
+    let rule = Rule::default();
+
     // P1
     let mut count = 0;
     for y in 0..input.len() {
         let row = input.get(y).unwrap();
         for x in 0..row.len() {
-            if input[y][x] && count_adjacent(x, y, &input) < 4 {
+            if rule.is_accessible(x, y, &input) {
                 count += 1;
             }
         }
@@ -60,21 +370,241 @@ pub fn solve() {
     println!("P1: {count}");
 
     // P2
-    let mut count = 0;
-    let mut keep_going = true;
-
-    while keep_going {
-        keep_going = false;
-        for y in 0..input.len() {
-            let row = input.get(y).unwrap();
-            for x in 0..row.len() {
-                if input[y][x] && count_adjacent(x, y, &input) < 4 {
-                    input[y][x] = false;
-                    keep_going = true;
-                    count += 1;
+    let count = removal_waves(&input, &rule)
+        .iter()
+        .flatten()
+        .filter(|wave| wave.is_some())
+        .count();
+    println!("P2: {count}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // The original rescan-until-stable loop
+    fn rescan(input: &mut [Vec<bool>]) -> usize {
+        let mut count = 0;
+        let mut keep_going = true;
+        while keep_going {
+            keep_going = false;
+            for y in 0..input.len() {
+                for x in 0..input[y].len() {
+                    if Rule::default().is_accessible(x, y, input) {
+                        input[y][x] = false;
+                        keep_going = true;
+                        count += 1;
+                    }
                 }
             }
         }
+        count
+    }
+
+    // Counts by walking every offset, wrapping or dropping it depending on the edges
+    fn naive_count(rule: &Rule, x: usize, y: usize, rolls: &[Vec<bool>]) -> u32 {
+        let (height, width) = (rolls.len() as i32, rolls[0].len() as i32);
+        let mut count = 0;
+        for (dy, dx) in &rule.offsets {
+            let (ny, nx) = match rule.edges {
+                Edges::Bounded => (y as i32 + dy, x as i32 + dx),
+                Edges::Toroidal => (
+                    (y as i32 + dy).rem_euclid(height),
+                    (x as i32 + dx).rem_euclid(width),
+                ),
+            };
+            if (0..height).contains(&ny) && (0..width).contains(&nx) {
+                count += rolls[ny as usize][nx as usize] as u32;
+            }
+        }
+        count
+    }
+
+    // Removes every accessible roll at once, one wave at a time
+    fn naive_waves(input: &[Vec<bool>], rule: &Rule) -> Vec<Vec<Option<u32>>> {
+        let mut rolls = input.to_vec();
+        let mut waves: Vec<Vec<Option<u32>>> =
+            input.iter().map(|row| vec![None; row.len()]).collect();
+        for wave in 1.. {
+            let removed: Vec<(usize, usize)> = (0..rolls.len())
+                .flat_map(|y| (0..rolls[y].len()).map(move |x| (y, x)))
+                .filter(|&(y, x)| {
+                    rolls[y][x] && rule.threshold.accepts(naive_count(rule, x, y, &rolls))
+                })
+                .collect();
+            if removed.is_empty() {
+                break;
+            }
+            for (y, x) in removed {
+                rolls[y][x] = false;
+                waves[y][x] = Some(wave);
+            }
+        }
+        waves
+    }
+
+    fn random_rule(rng: &mut Rng) -> Rule {
+        let neighbourhood = match rng.below(4) {
+            0 => Neighbourhood::VonNeumann,
+            1 => Neighbourhood::Moore,
+            2 => Neighbourhood::Radius(rng.range(0, 3) as u32),
+            // possibly asymmetric, duplicated or pointing at the cell itself
+            _ => Neighbourhood::Custom(
+                (0..rng.range(0, 8))
+                    .map(|_| (rng.range(0, 6) as i32 - 3, rng.range(0, 6) as i32 - 3))
+                    .collect(),
+            ),
+        };
+        let cmp = match rng.below(5) {
+            0 => Comparison::Less,
+            1 => Comparison::LessOrEqual,
+            2 => Comparison::Equal,
+            3 => Comparison::GreaterOrEqual,
+            _ => Comparison::Greater,
+        };
+        let value = rng.range(0, neighbourhood.offsets().len() + 1) as u32;
+        let edges = if rng.chance(0.5) {
+            Edges::Bounded
+        } else {
+            Edges::Toroidal
+        };
+        Rule::new(neighbourhood, Threshold { cmp, value }, edges)
+    }
+
+    fn random_grid(rng: &mut Rng) -> Vec<Vec<bool>> {
+        let height = rng.range(1, 20);
+        let width = rng.range(1, 20);
+        let density = rng.f64();
+        (0..height)
+            .map(|_| (0..width).map(|_| rng.chance(density)).collect())
+            .collect()
+    }
+
+    fn removed(waves: &[Vec<Option<u32>>]) -> usize {
+        waves.iter().flatten().filter(|wave| wave.is_some()).count()
+    }
+
+    #[test]
+    fn matches_rescan() {
+        let mut rng = Rng::new(27);
+        for _ in 0..1000 {
+            let grid = random_grid(&mut rng);
+            let waves = removal_waves(&grid, &Rule::default());
+            let mut expected = grid.clone();
+            assert_eq!(removed(&waves), rescan(&mut expected), "{grid:?}");
+            assert_eq!(rolls_after(&grid, &waves, u32::MAX), expected, "{grid:?}");
+        }
+    }
+
+    #[test]
+    fn matches_naive_waves() {
+        let mut rng = Rng::new(28);
+        for _ in 0..1000 {
+            let grid = random_grid(&mut rng);
+            let rule = Rule::default();
+            assert_eq!(
+                removal_waves(&grid, &rule),
+                naive_waves(&grid, &rule),
+                "{grid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rules_match_naive_waves() {
+        let mut rng = Rng::new(29);
+        for _ in 0..3000 {
+            let grid = random_grid(&mut rng);
+            let rule = random_rule(&mut rng);
+            assert_eq!(
+                removal_waves(&grid, &rule),
+                naive_waves(&grid, &rule),
+                "{rule:?} {grid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn asymmetric_offsets() {
+        // Only the cell to the right counts
+        let right = |edges| {
+            let threshold = Threshold {
+                cmp: Comparison::Less,
+                value: 1,
+            };
+            Rule::new(Neighbourhood::Custom(vec![(0, 1)]), threshold, edges)
+        };
+        let grid = vec![vec![true, true, true]];
+        assert_eq!(
+            removal_waves(&grid, &right(Edges::Bounded)),
+            vec![vec![Some(3), Some(2), Some(1)]]
+        );
+        // the last roll sees the first one
+        assert_eq!(
+            removal_waves(&grid, &right(Edges::Toroidal)),
+            vec![vec![None, None, None]]
+        );
+    }
+
+    #[test]
+    fn render() {
+        let grid = vec![vec![true; 3]; 3];
+        let waves = removal_waves(&grid, &Rule::default());
+        assert_eq!(
+            render_waves(&grid, &waves, WaveStyle::Number),
+            "121\n232\n121\n"
+        );
+        assert_eq!(
+            render_waves(&grid, &waves, WaveStyle::Gradient),
+            ".-.\n-*-\n.-.\n"
+        );
+
+        let grid = vec![vec![true, false, true, true, true]];
+        let waves = vec![vec![Some(9), None, Some(10), Some(36), None]];
+        assert_eq!(render_waves(&grid, &waves, WaveStyle::Number), "9 a+@\n");
+        assert_eq!(render_waves(&grid, &waves, WaveStyle::Gradient), ": -%@\n");
+    }
+
+    #[test]
+    fn frames() {
+        let rolls = vec![vec![true, false, true], vec![false, true, false]];
+        let dir = std::env::temp_dir().join(format!("aoc2025-d4-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("frame.txt");
+        write_frame(&path, &rolls, FrameFormat::Text).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "@.@\n.@.\n");
+
+        let path = dir.join("frame.ppm");
+        write_frame(&path, &rolls, FrameFormat::Ppm).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let header = b"P6\n12 8\n255\n";
+        assert!(bytes.starts_with(header));
+        assert_eq!(bytes.len(), header.len() + 12 * 8 * 3);
+
+        // Every 4x4 block has the colour of its roll
+        let pixels = &bytes[header.len()..];
+        for y in 0..8 {
+            for x in 0..12 {
+                let shade = if rolls[y / PPM_SCALE][x / PPM_SCALE] {
+                    255
+                } else {
+                    0
+                };
+                assert_eq!(pixels[(y * 12 + x) * 3..][..3], [shade; 3], "{y} {x}");
+            }
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn input() {
+        let grid: Vec<Vec<bool>> = include_str!("./input.txt")
+            .lines()
+            .map(|line| line.chars().map(|c| c == '@').collect())
+            .collect();
+        assert_eq!(removed(&removal_waves(&grid, &Rule::default())), 9609);
     }
-    println!("P2: {count}");
 }
@@ -1,39 +1,39 @@
-use std::ops::RangeInclusive;
+use crate::input;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    io::{self, BufRead},
+    ops::RangeInclusive,
+    str::FromStr,
+};
 
-pub fn solve() {
-    let input = include_str!("./input.txt");
+const TOP_RANGES: usize = 10;
 
-    let index = input.find("\n\n").unwrap();
-    let (ranges, ids) = input.split_at(index);
+fn parse(input: &str) -> (Vec<RangeInclusive<u64>>, Vec<u64>) {
+    let input = input::normalize(input);
+    let sections = input::sections(&input);
+    let [ranges, ids] = sections.as_slice() else {
+        panic!("invalid input");
+    };
 
-    let mut ranges: Vec<RangeInclusive<u64>> = ranges
-        .trim()
-        .split('\n')
+    let ranges: Vec<RangeInclusive<u64>> = ranges
+        .iter()
         .map(|range| {
-            let (start, end) = range.split_once('-').unwrap();
+            let (start, end) = range.trim().split_once('-').unwrap();
             start.parse().unwrap()..=end.parse().unwrap()
         })
         .collect();
 
-    let ids: Vec<u64> = ids
-        .trim()
-        .split('\n')
-        .map(|id| id.parse().unwrap())
-        .collect();
-
-    // P1
-    let mut count = 0;
-    for id in ids {
-        if ranges.iter().any(|range| range.contains(&id)) {
-            count += 1;
-        }
-    }
+    let ids: Vec<u64> = ids.iter().map(|id| id.trim().parse().unwrap()).collect();
 
-    println!("P1: {count}");
+    (ranges, ids)
+}
 
-    // P2
+// Sorted, non-overlapping ranges covering the same IDs as `ranges`
+fn merge_ranges(ranges: &[RangeInclusive<u64>]) -> Vec<RangeInclusive<u64>> {
+    let mut ranges = ranges.to_vec();
     ranges.sort_by_key(|range| *range.start());
-    let count: u64 = ranges
+    ranges
         .iter()
         .fold(vec![], |mut acc: Vec<RangeInclusive<u64>>, cur| {
             if let Some(last) = acc.last_mut() {
@@ -50,9 +50,503 @@ pub fn solve() {
             };
             acc
         })
+}
+
+// Binary search for the last range starting at or before `id`, `merged` must come from
+// `merge_ranges`
+pub fn is_fresh(merged: &[RangeInclusive<u64>], id: u64) -> bool {
+    let idx = merged.partition_point(|range| *range.start() <= id);
+    idx > 0 && merged[idx - 1].contains(&id)
+}
+
+// Walks `merged` and the sorted `ids` side by side instead of searching for every ID
+pub fn count_fresh_sorted(merged: &[RangeInclusive<u64>], ids: &[u64]) -> usize {
+    let mut ranges = merged.iter().peekable();
+    let mut count = 0;
+
+    for id in ids {
+        while ranges.next_if(|range| range.end() < id).is_some() {}
+        match ranges.peek() {
+            Some(range) if range.contains(id) => count += 1,
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    count
+}
+
+#[derive(Debug)]
+struct Report {
+    spoiled: Vec<u64>,
+    // IDs between two fresh ranges, as `start..=end`
+    gaps: Vec<RangeInclusive<u64>>,
+    // (input index, range, matching IDs), most hits first
+    hits: Vec<(usize, RangeInclusive<u64>, usize)>,
+    // (input index, range) of ranges fully covered by another single range
+    redundant: Vec<(usize, RangeInclusive<u64>)>,
+}
+
+impl Report {
+    fn new(ranges: &[RangeInclusive<u64>], ids: &[u64]) -> Self {
+        let merged = merge_ranges(ranges);
+
+        let mut sorted_ids = ids.to_vec();
+        sorted_ids.sort();
+
+        let spoiled = sorted_ids
+            .iter()
+            .filter(|id| !is_fresh(&merged, **id))
+            .copied()
+            .collect();
+
+        let gaps = merged
+            .windows(2)
+            // touching ranges aren't merged, but there's nothing between them
+            .filter(|pair| pair[0].end() + 1 < *pair[1].start())
+            .map(|pair| pair[0].end() + 1..=pair[1].start() - 1)
+            .collect();
+
+        let mut hits: Vec<_> = ranges
+            .iter()
+            .enumerate()
+            .map(|(idx, range)| {
+                let from = sorted_ids.partition_point(|id| id < range.start());
+                let to = sorted_ids.partition_point(|id| id <= range.end());
+                (idx, range.clone(), to - from)
+            })
+            .collect();
+        hits.sort_by_key(|(idx, _, count)| (std::cmp::Reverse(*count), *idx));
+
+        // With ranges sorted by start (longest first on ties), a range is covered as soon
+        // as an earlier one reaches at least as far
+        let mut by_start: Vec<(usize, &RangeInclusive<u64>)> = ranges.iter().enumerate().collect();
+        by_start
+            .sort_by_key(|(idx, range)| (*range.start(), std::cmp::Reverse(*range.end()), *idx));
+
+        let mut redundant = vec![];
+        let mut furthest: Option<u64> = None;
+        for (idx, range) in by_start {
+            if furthest.is_some_and(|end| *range.end() <= end) {
+                redundant.push((idx, range.clone()));
+            } else {
+                furthest = Some(*range.end());
+            }
+        }
+        redundant.sort_by_key(|(idx, _)| *idx);
+
+        Self {
+            spoiled,
+            gaps,
+            hits,
+            redundant,
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Spoiled IDs ({}):", self.spoiled.len())?;
+        for id in self.spoiled.iter() {
+            writeln!(f, "  {id}")?;
+        }
+
+        writeln!(f, "Gaps between fresh ranges ({}):", self.gaps.len())?;
+        for gap in self.gaps.iter() {
+            writeln!(
+                f,
+                "  {}-{} ({} IDs)",
+                gap.start(),
+                gap.end(),
+                gap.end() - gap.start() + 1
+            )?;
+        }
+
+        writeln!(f, "Ranges with most hits:")?;
+        for (idx, range, count) in self.hits.iter().take(TOP_RANGES) {
+            writeln!(
+                f,
+                "  #{}: {}-{} ({count} IDs)",
+                idx + 1,
+                range.start(),
+                range.end()
+            )?;
+        }
+
+        writeln!(f, "Redundant ranges ({}):", self.redundant.len())?;
+        for (idx, range) in self.redundant.iter() {
+            writeln!(f, "  #{}: {}-{}", idx + 1, range.start(), range.end())?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn report() {
+    let input = include_str!("./input.txt");
+    let (ranges, ids) = parse(input);
+
+    print!("{}", Report::new(&ranges, &ids));
+}
+
+// Fresh ranges kept merged at all times (touching ranges included), keyed by start
+#[derive(Debug, Default)]
+struct FreshDb {
+    ranges: BTreeMap<u64, u64>,
+    // u128 since `0-18446744073709551615` holds one ID more than a u64 can count
+    count: u128,
+}
+
+impl FreshDb {
+    fn insert_range(&mut self, start: u64, end: u64) {
+        self.ranges.insert(start, end);
+        self.count += (end - start) as u128 + 1;
+    }
+
+    fn remove_range(&mut self, start: u64) -> u64 {
+        let end = self.ranges.remove(&start).unwrap();
+        self.count -= (end - start) as u128 + 1;
+        end
+    }
+
+    fn add(&mut self, range: RangeInclusive<u64>) {
+        let (mut start, mut end) = range.into_inner();
+        if start > end {
+            return;
+        }
+
+        // extend backwards if the previous range overlaps or touches
+        if let Some((&s, &e)) = self.ranges.range(..=start).next_back()
+            && e.saturating_add(1) >= start
+        {
+            start = s;
+            end = end.max(e);
+        }
+
+        // swallow everything starting inside (or right after) the new range
+        let swallowed: Vec<u64> = self
+            .ranges
+            .range(start..=end.saturating_add(1))
+            .map(|(s, _)| *s)
+            .collect();
+        for s in swallowed {
+            end = end.max(self.remove_range(s));
+        }
+
+        self.insert_range(start, end);
+    }
+
+    fn remove(&mut self, range: RangeInclusive<u64>) {
+        let (start, end) = range.into_inner();
+        if start > end {
+            return;
+        }
+
+        let mut affected: Vec<u64> = vec![];
+        if let Some((&s, &e)) = self.ranges.range(..start).next_back()
+            && e >= start
+        {
+            affected.push(s);
+        }
+        affected.extend(self.ranges.range(start..=end).map(|(s, _)| *s));
+
+        for s in affected {
+            let e = self.remove_range(s);
+            // keep whatever sticks out on either side
+            if s < start {
+                self.insert_range(s, start - 1);
+            }
+            if e > end {
+                self.insert_range(end + 1, e);
+            }
+        }
+    }
+
+    fn contains(&self, id: u64) -> bool {
+        self.ranges
+            .range(..=id)
+            .next_back()
+            .is_some_and(|(_, end)| id <= *end)
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    Add(RangeInclusive<u64>),
+    Remove(RangeInclusive<u64>),
+    Query(u64),
+    Count,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse_id(s: &str) -> Result<u64, String> {
+            s.trim().parse().map_err(|_| format!("invalid ID: {s}"))
+        }
+        fn parse_range(s: &str) -> Result<RangeInclusive<u64>, String> {
+            let (start, end) = s
+                .split_once('-')
+                .ok_or_else(|| format!("invalid range: {s}"))?;
+            let (start, end) = (parse_id(start)?, parse_id(end)?);
+            if start > end {
+                return Err(format!("reversed range: {s}"));
+            }
+            Ok(start..=end)
+        }
+
+        let (cmd, arg) = s.trim().split_once(' ').unwrap_or((s.trim(), ""));
+        match (cmd, arg.trim()) {
+            ("add", arg) => Ok(Command::Add(parse_range(arg)?)),
+            ("remove", arg) => Ok(Command::Remove(parse_range(arg)?)),
+            ("query", arg) => Ok(Command::Query(parse_id(arg)?)),
+            ("count", "") => Ok(Command::Count),
+            _ => Err(format!("unknown command: {s}")),
+        }
+    }
+}
+
+// Starts from the puzzle ranges and applies `add a-b`, `remove a-b`, `query id` and
+// `count` commands from stdin, one per line
+pub fn interactive() {
+    let input = include_str!("./input.txt");
+    let (ranges, _) = parse(input);
+
+    let mut db = FreshDb::default();
+    for range in ranges {
+        db.add(range);
+    }
+
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match line.parse::<Command>() {
+            Ok(Command::Add(range)) => db.add(range),
+            Ok(Command::Remove(range)) => db.remove(range),
+            Ok(Command::Query(id)) => {
+                let state = if db.contains(id) { "fresh" } else { "spoiled" };
+                println!("{id}: {state}");
+            }
+            Ok(Command::Count) => println!("{}", db.count),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
+
+pub fn solve() {
+    let input = include_str!("./input.txt");
+    let (ranges, ids) = parse(input);
+    let merged = merge_ranges(&ranges);
+
+    // P1
+    let count = ids.iter().filter(|id| is_fresh(&merged, **id)).count();
+
+    println!("P1: {count}");
+
+    // P2
+    let count: u64 = merged
         .iter()
         .map(|range| range.end() - range.start() + 1)
         .sum();
 
     println!("P2: {count}");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn naive(ranges: &[RangeInclusive<u64>], id: u64) -> bool {
+        ranges.iter().any(|range| range.contains(&id))
+    }
+
+    fn random_ranges(rng: &mut Rng, max: usize) -> Vec<RangeInclusive<u64>> {
+        (0..rng.range(0, 10))
+            .map(|_| {
+                let start = rng.range(0, max) as u64;
+                start..=start + rng.range(0, 10) as u64
+            })
+            .collect()
+    }
+
+    #[test]
+    fn is_fresh_matches_naive() {
+        let mut rng = Rng::new(30);
+        for _ in 0..500 {
+            let ranges = random_ranges(&mut rng, 50);
+            let merged = merge_ranges(&ranges);
+            for id in 0..70 {
+                assert_eq!(is_fresh(&merged, id), naive(&ranges, id), "{ranges:?} {id}");
+            }
+        }
+    }
+
+    #[test]
+    fn count_fresh_sorted_matches_naive() {
+        let mut rng = Rng::new(5);
+        for _ in 0..500 {
+            let ranges = random_ranges(&mut rng, 50);
+            let merged = merge_ranges(&ranges);
+            let mut ids: Vec<u64> = (0..rng.range(0, 30))
+                .map(|_| rng.range(0, 70) as u64)
+                .collect();
+            ids.sort();
+
+            let expected = ids.iter().filter(|id| naive(&ranges, **id)).count();
+            assert_eq!(
+                count_fresh_sorted(&merged, &ids),
+                expected,
+                "{ranges:?} {ids:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn input() {
+        let (ranges, mut ids) = parse(include_str!("./input.txt"));
+        let merged = merge_ranges(&ranges);
+        ids.sort();
+
+        let expected = ids.iter().filter(|id| naive(&ranges, **id)).count();
+        assert_eq!(expected, 874);
+        assert_eq!(count_fresh_sorted(&merged, &ids), expected);
+        assert_eq!(
+            ids.iter().filter(|id| is_fresh(&merged, **id)).count(),
+            expected
+        );
+    }
+
+    const EXAMPLE: &str = "3-5\n10-14\n16-20\n12-18\n\n1\n5\n8\n11\n17\n32\n";
+
+    #[test]
+    fn report_example() {
+        let (ranges, ids) = parse(EXAMPLE);
+        let report = Report::new(&ranges, &ids);
+
+        assert_eq!(report.spoiled, [1, 8, 32]);
+        assert_eq!(report.gaps, [6..=9]);
+        assert_eq!(
+            report.hits,
+            [
+                (0, 3..=5, 1),
+                (1, 10..=14, 1),
+                (2, 16..=20, 1),
+                (3, 12..=18, 1)
+            ]
+        );
+        assert!(report.redundant.is_empty());
+
+        assert_eq!(
+            report.to_string(),
+            "Spoiled IDs (3):\n  1\n  8\n  32\n\
+             Gaps between fresh ranges (1):\n  6-9 (4 IDs)\n\
+             Ranges with most hits:\n  #1: 3-5 (1 IDs)\n  #2: 10-14 (1 IDs)\n  \
+             #3: 16-20 (1 IDs)\n  #4: 12-18 (1 IDs)\n\
+             Redundant ranges (0):\n"
+        );
+    }
+
+    #[test]
+    fn report_touching_and_duplicates() {
+        // 21-25 touches 16-20, the second 3-5 duplicates the first, 13-14 sits inside 10-14
+        let input = "3-5\n10-14\n16-20\n12-18\n21-25\n3-5\n13-14\n\n1\n5\n8\n11\n17\n32\n22\n13\n";
+        let (ranges, ids) = parse(input);
+        let report = Report::new(&ranges, &ids);
+
+        assert_eq!(report.spoiled, [1, 8, 32]);
+        assert_eq!(report.gaps, [6..=9]);
+        assert_eq!(
+            report.hits,
+            [
+                (1, 10..=14, 2),
+                (3, 12..=18, 2),
+                (0, 3..=5, 1),
+                (2, 16..=20, 1),
+                (4, 21..=25, 1),
+                (5, 3..=5, 1),
+                (6, 13..=14, 1),
+            ]
+        );
+        assert_eq!(report.redundant, [(5, 3..=5), (6, 13..=14)]);
+    }
+
+    fn naive_count(bits: &[bool]) -> u128 {
+        bits.iter().filter(|bit| **bit).count() as u128
+    }
+
+    #[test]
+    fn fresh_db_matches_bitset() {
+        let mut rng = Rng::new(32);
+        for _ in 0..500 {
+            let mut db = FreshDb::default();
+            let mut bits = [false; 64];
+            let mut commands = vec![];
+
+            for _ in 0..rng.range(1, 20) {
+                let start = rng.range(0, 60);
+                let end = rng.range(start, (start + 8).min(63));
+                if rng.chance(0.6) {
+                    commands.push(format!("add {start}-{end}"));
+                    db.add(start as u64..=end as u64);
+                    bits[start..=end].fill(true);
+                } else {
+                    commands.push(format!("remove {start}-{end}"));
+                    db.remove(start as u64..=end as u64);
+                    bits[start..=end].fill(false);
+                }
+
+                assert_eq!(db.count, naive_count(&bits), "{commands:?}");
+                for (id, bit) in bits.iter().enumerate() {
+                    assert_eq!(db.contains(id as u64), *bit, "{commands:?} {id}");
+                }
+                // stored ranges never overlap or touch
+                for (pair, next) in db.ranges.iter().zip(db.ranges.iter().skip(1)) {
+                    assert!(pair.1 + 1 < *next.0, "{commands:?} {:?}", db.ranges);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fresh_db_full_range() {
+        let mut db = FreshDb::default();
+        db.add(0..=u64::MAX);
+        assert_eq!(db.count, 1 << 64);
+        assert!(db.contains(u64::MAX));
+
+        db.remove(10..=u64::MAX);
+        assert_eq!(db.count, 10);
+        assert!(db.contains(9));
+        assert!(!db.contains(10));
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!("add 3-5".parse(), Ok(Command::Add(r)) if r == (3..=5)));
+        assert!(matches!(" remove  7-7 ".parse(), Ok(Command::Remove(r)) if r == (7..=7)));
+        assert!(matches!("query 12".parse(), Ok(Command::Query(12))));
+        assert!(matches!("count".parse(), Ok(Command::Count)));
+        assert!(matches!(
+            "add 0-18446744073709551615".parse(),
+            Ok(Command::Add(r)) if r == (0..=u64::MAX)
+        ));
+
+        let err = |s: &str| s.parse::<Command>().unwrap_err();
+        assert_eq!(err("add 5-3"), "reversed range: 5-3");
+        assert_eq!(err("remove 5-3"), "reversed range: 5-3");
+        assert_eq!(err("add 5"), "invalid range: 5");
+        assert_eq!(err("add 1-x"), "invalid ID: x");
+        assert_eq!(
+            err("add 0-18446744073709551616"),
+            "invalid ID: 18446744073709551616"
+        );
+        assert_eq!(err("query"), "invalid ID: ");
+        assert_eq!(err("count 3"), "unknown command: count 3");
+        assert_eq!(err("drop 1-2"), "unknown command: drop 1-2");
+    }
+}
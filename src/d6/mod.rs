@@ -1,7 +1,192 @@
+use crate::{big::BigUint, input};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Associativity {
+    // (a op b) op c
+    Left,
+    // a op (b op c), like exponentiation
+    Right,
+}
+
 #[derive(Debug)]
-enum Operator {
-    Addition,
-    Multiplication,
+struct Operator {
+    symbol: &'static str,
+    // `identity op x == x`, if there is one. Left folds start from it, so it only has to
+    // work from the left (`0 || x == x`, but `x || 0 == 10x`).
+    left_identity: Option<u64>,
+    associativity: Associativity,
+    // `None` on overflow or when the result is undefined (e.g. division by zero)
+    apply: fn(u64, u64) -> Option<u64>,
+    apply_big: fn(&BigUint, &BigUint) -> Option<BigUint>,
+}
+
+// Exact `^` results bigger than this count as an arithmetic error, rather than taking ages
+// (and all the memory) for a typo like `2 ^ 99999999999`
+const MAX_BIG_BITS: usize = 1 << 16;
+
+// Every symbol the worksheet understands, new operators only need an entry here
+const OPERATORS: &[Operator] = &[
+    Operator {
+        symbol: "+",
+        left_identity: Some(0),
+        associativity: Associativity::Left,
+        apply: u64::checked_add,
+        apply_big: |a, b| Some(a + b),
+    },
+    Operator {
+        symbol: "*",
+        left_identity: Some(1),
+        associativity: Associativity::Left,
+        apply: u64::checked_mul,
+        apply_big: |a, b| Some(a * b),
+    },
+    Operator {
+        symbol: "-",
+        left_identity: None,
+        associativity: Associativity::Left,
+        apply: u64::checked_sub,
+        apply_big: BigUint::checked_sub,
+    },
+    Operator {
+        symbol: "/",
+        left_identity: None,
+        associativity: Associativity::Left,
+        apply: u64::checked_div,
+        apply_big: |a, b| a.checked_div_rem(b).map(|(q, _)| q),
+    },
+    Operator {
+        symbol: "max",
+        left_identity: Some(u64::MIN),
+        associativity: Associativity::Left,
+        apply: |a, b| Some(a.max(b)),
+        apply_big: |a, b| Some(a.max(b).clone()),
+    },
+    Operator {
+        symbol: "min",
+        left_identity: Some(u64::MAX),
+        associativity: Associativity::Left,
+        apply: |a, b| Some(a.min(b)),
+        apply_big: |a, b| Some(a.min(b).clone()),
+    },
+    Operator {
+        symbol: "^",
+        left_identity: None,
+        associativity: Associativity::Right,
+        apply: |a, b| a.checked_pow(b.try_into().ok()?),
+        apply_big: |a, b| a.checked_pow(b.to_u64()?, MAX_BIG_BITS),
+    },
+    Operator {
+        symbol: "%",
+        left_identity: None,
+        associativity: Associativity::Left,
+        apply: u64::checked_rem,
+        apply_big: |a, b| a.checked_div_rem(b).map(|(_, r)| r),
+    },
+    // 12 || 34 == 1234
+    Operator {
+        symbol: "||",
+        left_identity: Some(0),
+        associativity: Associativity::Left,
+        apply: |a, b| {
+            let shift = 10u64.checked_pow(b.checked_ilog10().unwrap_or(0) + 1)?;
+            a.checked_mul(shift)?.checked_add(b)
+        },
+        apply_big: |a, b| Some(&(a * &BigUint::from(10).pow(b.digits())) + b),
+    },
+];
+
+#[derive(Debug, PartialEq)]
+enum FoldError {
+    Empty,
+    Arithmetic,
+}
+
+impl Operator {
+    fn from_symbol(symbol: &str) -> Option<&'static Operator> {
+        OPERATORS.iter().find(|op| op.symbol == symbol)
+    }
+
+    // Left folds start from the left identity when there is one (so an empty column is fine),
+    // otherwise from the first number. Right folds start from the last number and work back.
+    fn fold_with<T, I>(
+        &self,
+        numbers: I,
+        from_u64: impl Fn(u64) -> T,
+        apply: impl Fn(T, T) -> Option<T>,
+    ) -> Result<T, FoldError>
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        let mut numbers = numbers.into_iter();
+        match self.associativity {
+            Associativity::Left => {
+                let init = match self.left_identity {
+                    Some(identity) => from_u64(identity),
+                    None => numbers.next().ok_or(FoldError::Empty)?,
+                };
+                numbers.try_fold(init, |acc, cur| {
+                    apply(acc, cur).ok_or(FoldError::Arithmetic)
+                })
+            }
+            Associativity::Right => {
+                let mut numbers = numbers.rev();
+                let init = numbers.next().ok_or(FoldError::Empty)?;
+                numbers.try_fold(init, |acc, cur| {
+                    apply(cur, acc).ok_or(FoldError::Arithmetic)
+                })
+            }
+        }
+    }
+
+    fn fold(&self, numbers: Vec<u64>) -> Result<u64, FoldError> {
+        self.fold_with(numbers, |n| n, self.apply)
+    }
+
+    fn fold_big(&self, numbers: Vec<u64>) -> Result<BigUint, FoldError> {
+        self.fold_with(
+            numbers.into_iter().map(BigUint::from),
+            BigUint::from,
+            |a, b| (self.apply_big)(&a, &b),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum WorksheetError {
+    UnknownOperator { col: usize, symbol: String },
+    EmptyColumn { col: usize, symbol: &'static str },
+    Arithmetic { col: usize, symbol: &'static str },
+    TotalOverflow,
+}
+
+impl WorksheetError {
+    fn from_fold(e: FoldError, col: usize, symbol: &'static str) -> Self {
+        match e {
+            FoldError::Empty => WorksheetError::EmptyColumn { col, symbol },
+            FoldError::Arithmetic => WorksheetError::Arithmetic { col, symbol },
+        }
+    }
+}
+
+impl Display for WorksheetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WorksheetError::UnknownOperator { col, symbol } => {
+                write!(f, "unknown operator '{symbol}' in column {}", col + 1)
+            }
+            WorksheetError::EmptyColumn { col, symbol } => {
+                write!(f, "operator '{symbol}' in column {} needs numbers", col + 1)
+            }
+            WorksheetError::Arithmetic { col, symbol } => write!(
+                f,
+                "column {} ('{symbol}') overflowed or divided by zero",
+                col + 1
+            ),
+            WorksheetError::TotalOverflow => write!(f, "grand total overflowed"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -22,21 +207,64 @@ impl<'a> From<&NumAligned<'a>> for u64 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnOrder {
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DigitOrder {
+    TopToBottom,
+    BottomToTop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reading {
+    // Every row is a number (P1)
+    Rows,
+    // Every character column is a number (P2 is left to right, top to bottom)
+    Columns {
+        order: ColumnOrder,
+        digits: DigitOrder,
+    },
+}
+
+impl Reading {
+    pub const P1: Reading = Reading::Rows;
+    pub const P2: Reading = Reading::Columns {
+        order: ColumnOrder::LeftToRight,
+        digits: DigitOrder::TopToBottom,
+    };
+}
+
 #[derive(Debug)]
-struct Col<'a> {
-    op: Operator,
+pub struct Col<'a> {
+    op: &'static Operator,
     numbers: Vec<NumAligned<'a>>,
 }
 
 impl<'a> Col<'a> {
-    fn numbers_aligned(&self) -> Vec<u64> {
+    pub fn numbers(&self, reading: Reading) -> Vec<u64> {
+        match reading {
+            Reading::Rows => self.numbers.iter().map(u64::from).collect(),
+            Reading::Columns { order, digits } => {
+                let mut numbers = self.numbers_aligned(digits);
+                if order == ColumnOrder::RightToLeft {
+                    numbers.reverse();
+                }
+                numbers
+            }
+        }
+    }
+    fn numbers_aligned(&self, digits: DigitOrder) -> Vec<u64> {
         let longest = self.numbers.iter().map(|n| n.str.len()).max().unwrap();
         (0..longest)
-            .map(|n| self.num_at_pos_aligned(n, longest))
+            .map(|n| self.num_at_pos_aligned(n, longest, digits))
             .collect()
     }
-    fn num_at_pos_aligned(&self, pos: usize, longest: usize) -> u64 {
-        let numbers: Vec<_> = self
+    fn num_at_pos_aligned(&self, pos: usize, longest: usize, digits: DigitOrder) -> u64 {
+        let mut numbers: Vec<_> = self
             .numbers
             .iter()
             .filter_map(|num_str| {
@@ -58,6 +286,10 @@ impl<'a> Col<'a> {
             .map(|b| b - b'0') // convert byte to number
             .collect();
 
+        if digits == DigitOrder::BottomToTop {
+            numbers.reverse();
+        }
+
         let mut n = 0u64;
         for c in numbers {
             n = n * 10 + c as u64;
@@ -66,16 +298,18 @@ impl<'a> Col<'a> {
     }
 }
 
-pub fn solve() {
-    let input = include_str!("./input.txt");
-    let mut lines: Vec<&str> = input.split_terminator('\n').collect();
+// The last line holds the operators, and every line needs to be padded to the same width
+// (see `input::padded_lines`) so the columns line up
+pub fn parse<'a>(mut lines: Vec<&'a str>) -> Result<Vec<Col<'a>>, WorksheetError> {
+    let operators = lines.pop().unwrap_or_default();
 
-    let operators = lines.pop().unwrap();
+    // a column starts wherever an operator symbol does
     let split_idx: Vec<_> = operators
         .bytes()
         .enumerate()
         .filter_map(|(idx, b)| {
-            if b.is_ascii_whitespace() {
+            let prev_is_space = idx == 0 || operators.as_bytes()[idx - 1].is_ascii_whitespace();
+            if b.is_ascii_whitespace() || !prev_is_space {
                 None
             } else {
                 Some(idx)
@@ -117,45 +351,472 @@ pub fn solve() {
 
     for (idx, operator) in operators.iter().enumerate() {
         let numbers: Vec<NumAligned> = lines.iter().map(|line| line[idx]).collect();
-        cols.push(Col {
-            op: match *operator {
-                "+" => Operator::Addition,
-                "*" => Operator::Multiplication,
-                _ => panic!("invalid input"),
-            },
-            numbers,
-        });
-    }
-
-    // P1
-    let mut total = 0;
-    for col in cols.iter() {
-        total += col.numbers.iter().fold(
-            match col.op {
-                Operator::Multiplication => 1,
-                Operator::Addition => 0,
-            },
-            |acc, cur| match col.op {
-                Operator::Addition => acc + Into::<u64>::into(cur),
-                Operator::Multiplication => acc * Into::<u64>::into(cur),
-            },
-        );
-    }
-    println!("P1: {total}");
-
-    // P2
-    total = 0;
-    for col in cols.iter() {
-        total += col.numbers_aligned().iter().fold(
-            match col.op {
-                Operator::Addition => 0,
-                Operator::Multiplication => 1,
-            },
-            |acc, cur| match col.op {
-                Operator::Addition => acc + cur,
-                Operator::Multiplication => acc * cur,
-            },
-        );
-    }
-    println!("P2: {total}");
+        let op = Operator::from_symbol(operator).ok_or(WorksheetError::UnknownOperator {
+            col: idx,
+            symbol: operator.to_string(),
+        })?;
+        cols.push(Col { op, numbers });
+    }
+
+    Ok(cols)
+}
+
+pub fn grand_total(
+    cols: &[Col],
+    numbers: impl Fn(&Col) -> Vec<u64>,
+) -> Result<u64, WorksheetError> {
+    let mut total: u64 = 0;
+    for (idx, col) in cols.iter().enumerate() {
+        let result = col
+            .op
+            .fold(numbers(col))
+            .map_err(|e| WorksheetError::from_fold(e, idx, col.op.symbol))?;
+        total = total
+            .checked_add(result)
+            .ok_or(WorksheetError::TotalOverflow)?;
+    }
+    Ok(total)
+}
+
+fn grand_total_exact(
+    cols: &[Col],
+    numbers: impl Fn(&Col) -> Vec<u64>,
+) -> Result<BigUint, WorksheetError> {
+    let mut total = BigUint::zero();
+    for (idx, col) in cols.iter().enumerate() {
+        let result = col
+            .op
+            .fold_big(numbers(col))
+            .map_err(|e| WorksheetError::from_fold(e, idx, col.op.symbol))?;
+        total = total + result;
+    }
+    Ok(total)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum RenderFormat {
+    Text,
+    Markdown,
+}
+
+// One row per column: the numbers as read row by row (P1) next to the numbers read digit
+// column by digit column (P2), with both results. Columns where the two readings end up
+// with a different amount of numbers are flagged.
+fn render(cols: &[Col], format: RenderFormat) -> String {
+    fn join(numbers: &[u64]) -> String {
+        numbers
+            .iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+    fn result(col: &Col, numbers: &[u64]) -> String {
+        match col.op.fold(numbers.to_vec()) {
+            Ok(n) => n.to_string(),
+            Err(FoldError::Empty) => "empty".to_string(),
+            Err(FoldError::Arithmetic) => "overflow or /0".to_string(),
+        }
+    }
+
+    let header = ["col", "op", "P1 numbers", "P1", "P2 numbers", "P2", ""].map(String::from);
+    let mut rows = vec![header];
+
+    for (idx, col) in cols.iter().enumerate() {
+        let p1 = col.numbers(Reading::P1);
+        let p2 = col.numbers(Reading::P2);
+        let note = if p1.len() != p2.len() {
+            format!("{} vs {} numbers", p1.len(), p2.len())
+        } else {
+            String::new()
+        };
+        rows.push([
+            (idx + 1).to_string(),
+            col.op.symbol.to_string(),
+            join(&p1),
+            result(col, &p1),
+            join(&p2),
+            result(col, &p2),
+            note,
+        ]);
+    }
+
+    let mut out = String::new();
+    match format {
+        RenderFormat::Text => {
+            let widths: Vec<usize> = (0..rows[0].len())
+                .map(|i| rows.iter().map(|row| row[i].len()).max().unwrap_or(0))
+                .collect();
+            for row in rows.iter() {
+                let flagged = !row[6].is_empty();
+                let line: Vec<String> = row
+                    .iter()
+                    .zip(widths.iter())
+                    .map(|(cell, width)| format!("{cell:<width$}"))
+                    .collect();
+                let marker = if flagged { '!' } else { ' ' };
+                out.push_str(format!("{marker} {}", line.join("  ")).trim_end());
+                out.push('\n');
+            }
+        }
+        RenderFormat::Markdown => {
+            for (idx, row) in rows.iter().enumerate() {
+                let flagged = idx > 0 && !row[6].is_empty();
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| {
+                        // `|` (e.g. the concatenation operator) would end the cell
+                        let cell = cell.replace('|', "\\|");
+                        if flagged && !cell.is_empty() {
+                            format!("**{cell}**")
+                        } else {
+                            cell
+                        }
+                    })
+                    .collect();
+                out.push_str(&format!("| {} |\n", cells.join(" | ")));
+                if idx == 0 {
+                    out.push_str(&format!("|{}\n", "---|".repeat(row.len())));
+                }
+            }
+        }
+    }
+
+    out
+}
+
+fn print_total<T: Display>(part: &str, total: Result<T, WorksheetError>) {
+    match total {
+        Ok(total) => println!("{part}: {total}"),
+        Err(e) => eprintln!("{part}: {e}"),
+    }
+}
+
+// Parses the puzzle worksheet and hands the columns to `f`, or prints why it can't be parsed
+fn with_worksheet(f: impl FnOnce(&[Col])) {
+    let input = input::normalize(include_str!("./input.txt"));
+    let padded = input::padded_lines(&input);
+    let lines: Vec<&str> = padded.iter().map(String::as_str).collect();
+
+    match parse(lines) {
+        Ok(cols) => f(&cols),
+        Err(e) => eprintln!("{e}"),
+    }
+}
+
+pub fn solve() {
+    with_worksheet(|cols| {
+        // P1
+        print_total("P1", grand_total(cols, |col| col.numbers(Reading::P1)));
+
+        // P2
+        print_total("P2", grand_total(cols, |col| col.numbers(Reading::P2)));
+    });
+}
+
+// Same as `solve`, but with arbitrary precision so no column can overflow
+pub fn solve_exact() {
+    with_worksheet(|cols| {
+        // P1
+        print_total(
+            "P1",
+            grand_total_exact(cols, |col| col.numbers(Reading::P1)),
+        );
+
+        // P2
+        print_total(
+            "P2",
+            grand_total_exact(cols, |col| col.numbers(Reading::P2)),
+        );
+    });
+}
+
+// Prints every column with both readings side by side, to debug the alignment handling
+pub fn render_worksheet(format: RenderFormat) {
+    with_worksheet(|cols| print!("{}", render(cols, format)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Case = (&'static str, &'static [u64], Result<u64, FoldError>);
+
+    #[test]
+    fn operators() {
+        use FoldError::*;
+        let cases: &[Case] = &[
+            ("+", &[], Ok(0)),
+            ("+", &[1, 2, 3], Ok(6)),
+            ("+", &[u64::MAX, 1], Err(Arithmetic)),
+            ("*", &[], Ok(1)),
+            ("*", &[2, 3, 4], Ok(24)),
+            ("-", &[], Err(Empty)),
+            ("-", &[7], Ok(7)),
+            ("-", &[10, 3, 2], Ok(5)),
+            ("-", &[1, 2], Err(Arithmetic)),
+            ("/", &[], Err(Empty)),
+            ("/", &[100, 5, 2], Ok(10)),
+            ("/", &[1, 0], Err(Arithmetic)),
+            ("max", &[], Ok(0)),
+            ("max", &[3, 9, 4], Ok(9)),
+            ("min", &[], Ok(u64::MAX)),
+            ("min", &[3, 9, 4], Ok(3)),
+            ("^", &[], Err(Empty)),
+            ("^", &[5], Ok(5)),
+            // 2^(3^2), not (2^3)^2
+            ("^", &[2, 3, 2], Ok(512)),
+            ("^", &[0, 0], Ok(1)),
+            ("%", &[], Err(Empty)),
+            ("%", &[100, 30, 7], Ok(3)),
+            ("%", &[1, 0], Err(Arithmetic)),
+            ("||", &[], Ok(0)),
+            ("||", &[12, 34], Ok(1234)),
+            ("||", &[0, 5], Ok(5)),
+            ("||", &[5, 0], Ok(50)),
+            ("||", &[1, 0, 0], Ok(100)),
+            ("||", &[0, 0], Ok(0)),
+        ];
+
+        for op in OPERATORS {
+            assert!(
+                cases.iter().any(|(symbol, _, _)| *symbol == op.symbol),
+                "{} is untested",
+                op.symbol
+            );
+        }
+        for (symbol, numbers, expected) in cases {
+            let op = Operator::from_symbol(symbol).unwrap();
+            assert_eq!(op.fold(numbers.to_vec()), *expected, "{symbol} {numbers:?}");
+
+            // the exact fold agrees whenever nothing overflowed
+            let exact = op.fold_big(numbers.to_vec());
+            match expected {
+                Ok(n) => assert_eq!(exact, Ok(BigUint::from(*n)), "{symbol} {numbers:?}"),
+                Err(Empty) => assert_eq!(exact, Err(Empty), "{symbol} {numbers:?}"),
+                Err(Arithmetic) => {}
+            }
+        }
+    }
+
+    #[test]
+    fn exact_operators() {
+        let fold = |symbol, numbers: &[u64]| {
+            Operator::from_symbol(symbol)
+                .unwrap()
+                .fold_big(numbers.to_vec())
+                .map(|n| n.to_string())
+        };
+        assert_eq!(fold("+", &[u64::MAX, 1]).unwrap(), "18446744073709551616");
+        assert_eq!(
+            fold("^", &[2, 100]).unwrap(),
+            "1267650600228229401496703205376"
+        );
+        assert_eq!(fold("^", &[1, u64::MAX]).unwrap(), "1");
+        assert_eq!(fold("^", &[0, u64::MAX]).unwrap(), "0");
+        assert_eq!(fold("||", &[u64::MAX, 0]).unwrap(), "184467440737095516150");
+        assert_eq!(fold("/", &[1, 0]), Err(FoldError::Arithmetic));
+        assert_eq!(fold("%", &[1, 0]), Err(FoldError::Arithmetic));
+        assert_eq!(fold("-", &[1, 2]), Err(FoldError::Arithmetic));
+        // would need trillions of bits
+        assert_eq!(fold("^", &[2, 99_999_999_999]), Err(FoldError::Arithmetic));
+        assert_eq!(fold("^", &[3, 1 << 40]), Err(FoldError::Arithmetic));
+    }
+
+    #[test]
+    fn worksheet_errors() {
+        let rows = |col: &Col| col.numbers(Reading::P1);
+        let total = |lines: &[&str]| {
+            let cols = parse(lines.to_vec()).unwrap();
+            (grand_total(&cols, rows), grand_total_exact(&cols, rows))
+        };
+
+        // 2^99 only fits the exact total, 4 / 0 fits neither
+        let (fast, exact) = total(&["1 2  4", "2 99 0", "+ ^  /"]);
+        assert!(
+            matches!(
+                fast,
+                Err(WorksheetError::Arithmetic {
+                    col: 1,
+                    symbol: "^"
+                })
+            ),
+            "{fast:?}"
+        );
+        assert!(
+            matches!(
+                exact,
+                Err(WorksheetError::Arithmetic {
+                    col: 2,
+                    symbol: "/"
+                })
+            ),
+            "{exact:?}"
+        );
+
+        let (fast, exact) = total(&["1 2          ", "2 99999999999", "+ ^          "]);
+        assert!(
+            matches!(fast, Err(WorksheetError::Arithmetic { col: 1, .. })),
+            "{fast:?}"
+        );
+        assert!(
+            matches!(exact, Err(WorksheetError::Arithmetic { col: 1, .. })),
+            "{exact:?}"
+        );
+        assert_eq!(
+            exact.unwrap_err().to_string(),
+            "column 2 ('^') overflowed or divided by zero"
+        );
+
+        let (fast, exact) = total(&["18446744073709551615 1", "+                    +"]);
+        assert!(
+            matches!(fast, Err(WorksheetError::TotalOverflow)),
+            "{fast:?}"
+        );
+        assert_eq!(exact.unwrap().to_string(), "18446744073709551616");
+    }
+
+    #[test]
+    fn unknown_operator() {
+        assert!(Operator::from_symbol("?").is_none());
+        let err = parse(vec!["1 2 3", "+ ? *"]).unwrap_err();
+        assert!(
+            matches!(&err, WorksheetError::UnknownOperator { col: 1, symbol } if symbol == "?"),
+            "{err:?}"
+        );
+        assert_eq!(err.to_string(), "unknown operator '?' in column 2");
+    }
+
+    // `||` to exercise the escaping, `+` and `/` read as a different amount of numbers
+    const RENDER_EXAMPLE: [&str; 3] = ["12 4 1", " 3 5 0", "|| + /"];
+
+    #[test]
+    fn render_text() {
+        let cols = parse(RENDER_EXAMPLE.to_vec()).unwrap();
+        assert_eq!(
+            render(&cols, RenderFormat::Text),
+            "  col  op  P1 numbers  P1              P2 numbers  P2\n\
+             \x20 1    ||  12 3        123             1 23        123\n\
+             ! 2    +   4 5         9               45          45   2 vs 1 numbers\n\
+             ! 3    /   1 0         overflow or /0  10          10   2 vs 1 numbers\n"
+        );
+    }
+
+    #[test]
+    fn render_markdown() {
+        let cols = parse(RENDER_EXAMPLE.to_vec()).unwrap();
+        assert_eq!(
+            render(&cols, RenderFormat::Markdown),
+            "| col | op | P1 numbers | P1 | P2 numbers | P2 |  |\n\
+             |---|---|---|---|---|---|---|\n\
+             | 1 | \\|\\| | 12 3 | 123 | 1 23 | 123 |  |\n\
+             | **2** | **+** | **4 5** | **9** | **45** | **45** | **2 vs 1 numbers** |\n\
+             | **3** | **/** | **1 0** | **overflow or /0** | **10** | **10** | **2 vs 1 numbers** |\n"
+        );
+    }
+
+    const EXAMPLE: &str = "123 328  51 64\n 45 64  387 23\n  6 98  215 314\n*   +   *   +\n";
+
+    fn check(text: &str, reading: Reading, numbers: &[&[u64]], total: Option<u64>) {
+        let padded = input::padded_lines(text);
+        let cols = parse(padded.iter().map(String::as_str).collect()).unwrap();
+
+        let read: Vec<Vec<u64>> = cols.iter().map(|col| col.numbers(reading)).collect();
+        assert_eq!(read, numbers, "{reading:?}");
+        assert_eq!(
+            grand_total(&cols, |col| col.numbers(reading)).ok(),
+            total,
+            "{reading:?}"
+        );
+    }
+
+    #[test]
+    fn rows() {
+        check(
+            EXAMPLE,
+            Reading::P1,
+            &[
+                &[123, 45, 6],
+                &[328, 64, 98],
+                &[51, 387, 215],
+                &[64, 23, 314],
+            ],
+            Some(4277556),
+        );
+    }
+
+    #[test]
+    fn left_to_right_top_to_bottom() {
+        check(
+            EXAMPLE,
+            Reading::P2,
+            &[
+                &[1, 24, 356],
+                &[369, 248, 8],
+                &[32, 581, 175],
+                &[623, 431, 4],
+            ],
+            Some(3263827),
+        );
+    }
+
+    #[test]
+    fn right_to_left_top_to_bottom() {
+        let reading = Reading::Columns {
+            order: ColumnOrder::RightToLeft,
+            digits: DigitOrder::TopToBottom,
+        };
+        check(
+            EXAMPLE,
+            reading,
+            &[
+                &[356, 24, 1],
+                &[8, 248, 369],
+                &[175, 581, 32],
+                &[4, 431, 623],
+            ],
+            Some(3263827),
+        );
+        // the order only matters to operators that aren't commutative
+        check("12\n34\n- \n", reading, &[&[24, 13]], Some(11));
+    }
+
+    #[test]
+    fn left_to_right_bottom_to_top() {
+        let reading = Reading::Columns {
+            order: ColumnOrder::LeftToRight,
+            digits: DigitOrder::BottomToTop,
+        };
+        check(
+            EXAMPLE,
+            reading,
+            &[
+                &[1, 42, 653],
+                &[963, 842, 8],
+                &[23, 185, 571],
+                &[326, 134, 4],
+            ],
+            Some(2459308),
+        );
+        check("12\n34\n- \n", reading, &[&[31, 42]], None);
+    }
+
+    #[test]
+    fn right_to_left_bottom_to_top() {
+        let reading = Reading::Columns {
+            order: ColumnOrder::RightToLeft,
+            digits: DigitOrder::BottomToTop,
+        };
+        check(
+            EXAMPLE,
+            reading,
+            &[
+                &[653, 42, 1],
+                &[8, 842, 963],
+                &[571, 185, 23],
+                &[4, 134, 326],
+            ],
+            Some(2459308),
+        );
+        check("12\n34\n- \n", reading, &[&[42, 31]], Some(11));
+    }
 }
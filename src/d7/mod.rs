@@ -1,7 +1,11 @@
+use crate::{big::BigUint, input};
 use std::{
+    collections::HashSet,
     fmt::{self, Display, Formatter},
-    fs::File,
-    io::{BufRead, BufReader},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
 };
 
 #[derive(Clone, Copy, PartialEq)]
@@ -10,139 +14,587 @@ enum Thing {
     Air,
     Splitter,
     Beam,
+    // Sends the beam to the left only
+    DeflectorLeft,
+    // Sends the beam to the right only
+    DeflectorRight,
+    Absorber,
+    // Left, right and straight through
+    TripleSplitter,
+}
+
+impl Thing {
+    // Where a beam hitting this ends up, relative to its column. `None` for things the beam
+    // just falls through.
+    fn outputs(&self) -> Option<&'static [isize]> {
+        match self {
+            Thing::Start | Thing::Air | Thing::Beam => None,
+            Thing::Splitter => Some(&[-1, 1]),
+            Thing::DeflectorLeft => Some(&[-1]),
+            Thing::DeflectorRight => Some(&[1]),
+            Thing::Absorber => Some(&[]),
+            Thing::TripleSplitter => Some(&[-1, 0, 1]),
+        }
+    }
+
+    fn is_splitter(&self) -> bool {
+        self.outputs().is_some_and(|outputs| outputs.len() > 1)
+    }
+}
+
+// What happens to a beam that a splitter sends past the left or right edge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary {
+    // The beam is lost, its timelines never reach the floor
+    Absorb,
+    // The beam bounces off the wall back into the edge column
+    Reflect,
+    // The beam comes back in on the opposite side
+    Wrap,
+    // The beam counts as having reached the floor
+    Floor,
+}
+
+// Where a beam sent sideways ends up
+enum Landing {
+    Col(usize),
+    Floor,
+    Absorbed,
+}
+
+// What happened during one `apply_gravity`
+#[derive(Debug, Default)]
+struct Step {
+    // splitters that were hit
+    fired: Vec<(usize, usize)>,
+    // beams that ended up on a spot another beam already took
+    merged: usize,
 }
 
 #[derive(Clone, PartialEq)]
-struct Manifold {
+pub struct Manifold {
     shaft: Vec<Vec<Thing>>,
     spawn_point: (usize, usize),
     beams: Vec<(usize, usize)>,
     split_count: u32,
+    boundary: Boundary,
 }
 
-impl Manifold {
-    fn new() -> Self {
-        let f = File::open("./src/d7/input.txt").unwrap();
-        let reader = BufReader::new(f);
-        let mut spawn_point = (0, 0);
+#[derive(Debug, PartialEq)]
+pub enum ManifoldError {
+    Empty,
+    MissingStart,
+    MultipleStarts {
+        first: (usize, usize),
+        second: (usize, usize),
+    },
+    UnknownChar {
+        y: usize,
+        x: usize,
+        c: char,
+    },
+    RaggedRow {
+        y: usize,
+        expected: usize,
+        found: usize,
+    },
+    // the beam spawns below `S`, so it can't be on the last row
+    StartOnLastRow {
+        x: usize,
+    },
+}
 
-        let shaft = reader
-            .lines()
+impl Display for ManifoldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifoldError::Empty => write!(f, "empty manifold"),
+            ManifoldError::MissingStart => write!(f, "no start ('S') in manifold"),
+            ManifoldError::MultipleStarts { first, second } => write!(
+                f,
+                "multiple starts ('S') at row {}, col {} and row {}, col {}",
+                first.0 + 1,
+                first.1 + 1,
+                second.0 + 1,
+                second.1 + 1
+            ),
+            ManifoldError::UnknownChar { y, x, c } => {
+                write!(f, "unknown character '{c}' at row {}, col {}", y + 1, x + 1)
+            }
+            ManifoldError::RaggedRow { y, expected, found } => {
+                write!(f, "row {} is {found} wide, expected {expected}", y + 1)
+            }
+            ManifoldError::StartOnLastRow { x } => {
+                write!(f, "start ('S') at col {} is on the last row", x + 1)
+            }
+        }
+    }
+}
+
+impl FromStr for Manifold {
+    type Err = ManifoldError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = input::normalize(s);
+        let lines = input::lines(&s);
+        let width = lines.first().ok_or(ManifoldError::Empty)?.chars().count();
+        let mut start: Option<(usize, usize)> = None;
+
+        let shaft = lines
+            .iter()
             .enumerate()
             .map(|(y, line)| {
-                line.unwrap()
+                let row = line
                     .chars()
                     .enumerate()
                     .map(|(x, c)| match c {
                         'S' => {
-                            spawn_point = (y + 1, x);
-                            Thing::Start
+                            if let Some(first) = start {
+                                return Err(ManifoldError::MultipleStarts {
+                                    first,
+                                    second: (y, x),
+                                });
+                            }
+                            start = Some((y, x));
+                            Ok(Thing::Start)
                         }
-                        '.' => Thing::Air,
-                        '^' => Thing::Splitter,
-                        _ => panic!("invalid input"),
+                        '.' => Ok(Thing::Air),
+                        '^' => Ok(Thing::Splitter),
+                        '<' => Ok(Thing::DeflectorLeft),
+                        '>' => Ok(Thing::DeflectorRight),
+                        '#' => Ok(Thing::Absorber),
+                        '*' => Ok(Thing::TripleSplitter),
+                        c => Err(ManifoldError::UnknownChar { y, x, c }),
                     })
-                    .collect()
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                if row.len() != width {
+                    return Err(ManifoldError::RaggedRow {
+                        y,
+                        expected: width,
+                        found: row.len(),
+                    });
+                }
+                Ok(row)
             })
-            .collect();
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (y, x) = start.ok_or(ManifoldError::MissingStart)?;
+        if y + 1 >= shaft.len() {
+            return Err(ManifoldError::StartOnLastRow { x });
+        }
 
-        Self {
+        Ok(Self {
             shaft,
-            spawn_point,
+            spawn_point: (y + 1, x),
             beams: vec![],
             split_count: 0,
+            boundary: Boundary::Floor,
+        })
+    }
+}
+
+impl TryFrom<&str> for Manifold {
+    type Error = ManifoldError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl Manifold {
+    fn new() -> Self {
+        let input = fs::read_to_string("./src/d7/input.txt").unwrap();
+        match input.parse() {
+            Ok(manifold) => manifold,
+            Err(e) => panic!("invalid input: {e}"),
+        }
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    fn land(&self, x: usize, dx: isize) -> Landing {
+        let max_x = self.shaft[0].len() as isize;
+        let x = x as isize + dx;
+        if (0..max_x).contains(&x) {
+            return Landing::Col(x as usize);
+        }
+
+        match self.boundary {
+            Boundary::Absorb => Landing::Absorbed,
+            Boundary::Floor => Landing::Floor,
+            Boundary::Reflect => Landing::Col(if x < 0 { 0 } else { max_x as usize - 1 }),
+            Boundary::Wrap => Landing::Col(x.rem_euclid(max_x) as usize),
         }
     }
 
     fn spawn_beam(&mut self) {
         let (y, x) = self.spawn_point;
-        self.beams.push((y, x));
-        self.shaft[y][x] = Thing::Beam;
+        if self.shaft[y][x] == Thing::Air {
+            self.beams.push((y, x));
+            self.shaft[y][x] = Thing::Beam;
+        } else {
+            // the beam starts at `S` instead, so the next tick runs it into whatever is below
+            self.beams.push((y - 1, x));
+        }
     }
 
+    // Done once the beams reach the last row, or when they're all gone
     fn is_out_of_bounds(&self) -> bool {
-        self.shaft.last().unwrap().contains(&Thing::Beam)
+        let max_y = self.shaft.len();
+        self.beams.is_empty() || self.beams.iter().any(|(y, _)| y + 1 == max_y)
     }
 
-    fn apply_gravity(&mut self) {
+    fn apply_gravity(&mut self) -> Step {
         let mut new_beams: Vec<(usize, usize)> = vec![];
+        let mut step = Step::default();
 
         for beam in self.beams.iter() {
-            let (y, x) = beam;
-            self.shaft[*y][*x] = Thing::Air;
-
-            let next_thing = self.shaft[y + 1][*x];
-            match next_thing {
-                Thing::Start => unreachable!("nope"),
-                Thing::Beam => {}
-                Thing::Air => {
-                    self.shaft[y + 1][*x] = Thing::Beam;
-                    new_beams.push((y + 1, *x));
-                }
-                Thing::Splitter => {
-                    self.split_count += 1;
-                    self.shaft[y + 1][x - 1] = Thing::Beam;
-                    self.shaft[y + 1][x + 1] = Thing::Beam;
-                    new_beams.push((y + 1, x - 1));
-                    new_beams.push((y + 1, x + 1));
+            let (y, x) = *beam;
+            if self.shaft[y][x] == Thing::Beam {
+                self.shaft[y][x] = Thing::Air;
+            }
+
+            let next_thing = self.shaft[y + 1][x];
+            match (next_thing, next_thing.outputs()) {
+                (Thing::Start, _) => unreachable!("nope"),
+                (_, None) => new_beams.push((y + 1, x)),
+                (_, Some(outputs)) => {
+                    if next_thing.is_splitter() {
+                        self.split_count += 1;
+                        step.fired.push((y + 1, x));
+                    }
+                    for dx in outputs {
+                        if let Landing::Col(x) = self.land(x, *dx) {
+                            new_beams.push((y + 1, x));
+                        }
+                    }
                 }
             }
         }
 
+        // beams landing on the same spot merge
+        let before = new_beams.len();
+        new_beams.sort();
+        new_beams.dedup();
+        step.merged = before - new_beams.len();
+        for (y, x) in new_beams.iter() {
+            // a beam sent onto a splitter (or anything else) passes over it untouched
+            if self.shaft[*y][*x] == Thing::Air {
+                self.shaft[*y][*x] = Thing::Beam;
+            }
+        }
+
         self.beams = new_beams;
+        step
+    }
+
+    // Runs the P1 simulation, keeping a snapshot of every tick (the first one being the
+    // freshly spawned beam)
+    fn trace(mut self) -> Trace {
+        self.spawn_beam();
+        let mut ticks = vec![Tick {
+            manifold: self.clone(),
+            step: Step::default(),
+        }];
+
+        while !self.is_out_of_bounds() {
+            let step = self.apply_gravity();
+            ticks.push(Tick {
+                manifold: self.clone(),
+                step,
+            });
+        }
+
+        Trace { ticks }
     }
 
-    pub fn count_worlds(&self) -> u128 {
+    // P1: how many times a splitter gets hit
+    pub fn count_splits(&self) -> u32 {
+        let mut manifold = self.clone();
+        manifold.spawn_beam();
+        while !manifold.is_out_of_bounds() {
+            manifold.apply_gravity();
+        }
+        manifold.split_count
+    }
+
+    pub fn count_worlds(&self) -> Result<u128, Overflow> {
+        self.count_worlds_as()
+    }
+
+    fn count_worlds_as<C: Counter>(&self) -> Result<C, Overflow> {
+        self.worlds()?.total()
+    }
+
+    fn worlds<C: Counter>(&self) -> Result<Worlds<C>, Overflow> {
         let max_y = self.shaft.len();
         let max_x = self.shaft[0].len();
 
         // Current state:
         // how many worlds are currently at a given position
-        let mut worlds = vec![vec![0u128; max_x]; max_y];
+        let mut worlds = vec![vec![C::zero(); max_x]; max_y];
 
         let (spawn_y, spawn_x) = self.spawn_point;
-        worlds[spawn_y][spawn_x] = 1;
+        worlds[spawn_y][spawn_x] = C::one();
 
-        let mut worlds_at_floor: u128 = 0;
+        let mut floor = vec![C::zero(); max_x];
+        let mut sides = [C::zero(), C::zero()];
 
         for y in 0..max_y {
             for x in 0..max_x {
-                let count = worlds[y][x];
-                if count == 0 {
+                if worlds[y][x].is_zero() {
                     continue;
                 }
+                let count = worlds[y][x].clone();
+                let add = |to: &mut C| -> Result<(), Overflow> {
+                    *to = to.checked_add(&count).ok_or(Overflow::Cell { y, x })?;
+                    Ok(())
+                };
 
-                match self.shaft[y][x] {
-                    Thing::Start | Thing::Air | Thing::Beam => {
+                match self.shaft[y][x].outputs() {
+                    None => {
                         if y + 1 < max_y {
-                            worlds[y + 1][x] += count;
+                            add(&mut worlds[y + 1][x])?;
                         } else {
                             // this branch is at bottom
-                            worlds_at_floor += count;
+                            add(&mut floor[x])?;
                         }
                     }
-                    Thing::Splitter => {
-                        // Left branch
-                        if y + 1 < max_y && x > 0 {
-                            worlds[y + 1][x - 1] += count;
-                        } else {
-                            // this branch is at bottom
-                            worlds_at_floor += count;
-                        }
-
-                        // Right branch
-                        if y + 1 < max_y && x + 1 < max_x {
-                            worlds[y + 1][x + 1] += count;
-                        } else {
-                            // this branch is at bottom
-                            worlds_at_floor += count;
+                    Some(outputs) => {
+                        // every branch carries all the worlds
+                        for dx in outputs {
+                            match self.land(x, *dx) {
+                                Landing::Col(x) if y + 1 < max_y => add(&mut worlds[y + 1][x])?,
+                                // this branch is at bottom
+                                Landing::Col(x) => add(&mut floor[x])?,
+                                Landing::Floor => add(&mut sides[(*dx > 0) as usize])?,
+                                Landing::Absorbed => {}
+                            }
                         }
                     }
                 }
             }
         }
 
-        worlds_at_floor
+        Ok(Worlds {
+            table: worlds,
+            floor,
+            sides,
+        })
+    }
+}
+
+// Anything the timelines can be counted with
+trait Counter: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn is_zero(&self) -> bool;
+    // `None` when the result doesn't fit
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+}
+
+impl Counter for u128 {
+    fn zero() -> Self {
+        0
+    }
+    fn one() -> Self {
+        1
+    }
+    fn is_zero(&self) -> bool {
+        *self == 0
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u128::checked_add(*self, *other)
+    }
+}
+
+// Exact, however many timelines there are
+impl Counter for BigUint {
+    fn zero() -> Self {
+        BigUint::zero()
+    }
+    fn one() -> Self {
+        BigUint::from(1)
+    }
+    fn is_zero(&self) -> bool {
+        BigUint::is_zero(self)
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+}
+
+// Only whether any timeline gets somewhere
+impl Counter for bool {
+    fn zero() -> Self {
+        false
+    }
+    fn one() -> Self {
+        true
+    }
+    fn is_zero(&self) -> bool {
+        !*self
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(*self || *other)
+    }
+}
+
+// Timelines counted mod P, never overflows
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Modular<const P: u64>(u64);
+
+const MODULUS: u64 = 1_000_000_007;
+
+impl<const P: u64> Counter for Modular<P> {
+    fn zero() -> Self {
+        Modular(0)
+    }
+    fn one() -> Self {
+        Modular(1 % P)
+    }
+    // a cell that's 0 mod P gets skipped like an empty one, which is fine since it adds
+    // nothing further down either
+    fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(Modular(
+            ((self.0 as u128 + other.0 as u128) % P as u128) as u64,
+        ))
+    }
+}
+
+impl<const P: u64> Display for Modular<P> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (mod {P})", self.0)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Overflow {
+    // while passing on the timelines from this cell
+    Cell { y: usize, x: usize },
+    // while adding up everything that reached the floor
+    Total,
+}
+
+impl Display for Overflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Overflow::Cell { y, x } => write!(
+                f,
+                "timeline count overflowed at row {}, col {}",
+                y + 1,
+                x + 1
+            ),
+            Overflow::Total => write!(f, "total timeline count overflowed"),
+        }
+    }
+}
+
+// The P2 table: how many timelines pass through every cell, and where they end up
+struct Worlds<C> {
+    table: Vec<Vec<C>>,
+    // timelines leaving through the bottom, per column
+    floor: Vec<C>,
+    // timelines leaving through the left and right side, with `Boundary::Floor`
+    sides: [C; 2],
+}
+
+const HEAT: &[u8] = b" .:-=+*#%@";
+
+impl<C: Counter> Worlds<C> {
+    fn total(&self) -> Result<C, Overflow> {
+        self.floor
+            .iter()
+            .chain(self.sides.iter())
+            .try_fold(C::zero(), |acc, cur| acc.checked_add(cur))
+            .ok_or(Overflow::Total)
+    }
+}
+
+impl Worlds<u128> {
+    fn max(&self) -> u128 {
+        self.table.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    // 0.0 for no timelines up to 1.0 for the busiest cell (`max`), on a log scale
+    fn heat(count: u128, max: u128) -> f64 {
+        if count == 0 || max == 0 {
+            return 0.0;
+        }
+        ((count as f64).ln() + 1.0) / ((max as f64).ln() + 1.0)
+    }
+
+    fn heatmap(&self) -> String {
+        let max = self.max();
+        let mut out = String::new();
+        for row in self.table.iter() {
+            for count in row {
+                let idx = if *count == 0 {
+                    0
+                } else {
+                    // anything with timelines gets at least the lightest character
+                    1 + (Self::heat(*count, max) * (HEAT.len() - 2) as f64).round() as usize
+                };
+                out.push(HEAT[idx] as char);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn write_pgm(&self, path: &Path) -> io::Result<()> {
+        let mut f = BufWriter::new(File::create(path)?);
+        let height = self.table.len();
+        let width = self.table.first().map_or(0, |row| row.len());
+        write!(f, "P5\n{width} {height}\n255\n")?;
+
+        let max = self.max();
+        let pixels: Vec<u8> = self
+            .table
+            .iter()
+            .flatten()
+            .map(|count| (Self::heat(*count, max) * 255.0).round() as u8)
+            .collect();
+        f.write_all(&pixels)?;
+        f.flush()
+    }
+}
+
+struct Tick {
+    manifold: Manifold,
+    step: Step,
+}
+
+struct Trace {
+    ticks: Vec<Tick>,
+}
+
+impl Trace {
+    fn never_hit(&self) -> Vec<(usize, usize)> {
+        let Some(first) = self.ticks.first() else {
+            return vec![];
+        };
+        let fired: HashSet<(usize, usize)> = self
+            .ticks
+            .iter()
+            .flat_map(|tick| tick.step.fired.iter().copied())
+            .collect();
+
+        let mut never_hit = vec![];
+        for (y, row) in first.manifold.shaft.iter().enumerate() {
+            for (x, thing) in row.iter().enumerate() {
+                if thing.is_splitter() && !fired.contains(&(y, x)) {
+                    never_hit.push((y, x));
+                }
+            }
+        }
+        never_hit
     }
 }
 
@@ -153,6 +605,10 @@ impl Display for Thing {
             Thing::Air => '.',
             Thing::Splitter => '^',
             Thing::Beam => '|',
+            Thing::DeflectorLeft => '<',
+            Thing::DeflectorRight => '>',
+            Thing::Absorber => '#',
+            Thing::TripleSplitter => '*',
         };
 
         write!(f, "{}", c)
@@ -172,16 +628,394 @@ impl Display for Manifold {
 }
 
 pub fn solve() {
+    solve_with_boundary(Boundary::Floor);
+}
+
+pub fn solve_with_boundary(boundary: Boundary) {
     // P1
-    let mut manifold = Manifold::new();
-    manifold.spawn_beam();
+    let manifold = Manifold::new().with_boundary(boundary);
+    println!("Count: {}", manifold.count_splits());
 
-    while !manifold.is_out_of_bounds() {
-        manifold.apply_gravity();
+    // P2
+    let manifold = Manifold::new().with_boundary(boundary);
+    match manifold.count_worlds() {
+        Ok(count) => println!("Count: {count}"),
+        Err(e) => println!("Count: {e}"),
     }
-    println!("Count: {}", manifold.split_count);
+}
 
-    // P2
+// P2 with every counter, the modular and exact ones can't overflow
+pub fn solve_counts() {
     let manifold = Manifold::new();
-    println!("Count: {}", manifold.count_worlds());
+
+    match manifold.count_worlds_as::<u128>() {
+        Ok(count) => println!("u128: {count}"),
+        Err(e) => println!("u128: {e}"),
+    }
+    if let Ok(count) = manifold.count_worlds_as::<Modular<MODULUS>>() {
+        println!("Modular: {count}");
+    }
+    if let Ok(count) = manifold.count_worlds_as::<BigUint>() {
+        println!("Exact: {count}");
+    }
+}
+
+// P1 again, tick by tick
+pub fn solve_trace(print_frames: bool) {
+    let trace = Manifold::new().trace();
+
+    for (idx, tick) in trace.ticks.iter().enumerate() {
+        println!(
+            "Tick {idx}: {} beams, {} splitters fired, {} merged",
+            tick.manifold.beams.len(),
+            tick.step.fired.len(),
+            tick.step.merged
+        );
+        if print_frames {
+            println!("{}", tick.manifold);
+        }
+    }
+
+    let never_hit = trace.never_hit();
+    println!("Never hit: {} splitters", never_hit.len());
+    for (y, x) in never_hit {
+        println!("  row {}, col {}", y + 1, x + 1);
+    }
+}
+
+// Where the P2 timelines go, optionally saved as a grayscale image too
+pub fn solve_heatmap(pgm: Option<&Path>) -> io::Result<()> {
+    let worlds = match Manifold::new().worlds::<u128>() {
+        Ok(worlds) => worlds,
+        Err(e) => {
+            eprintln!("{e}");
+            return Ok(());
+        }
+    };
+
+    print!("{}", worlds.heatmap());
+
+    println!("Floor:");
+    for (x, count) in worlds.floor.iter().enumerate() {
+        if *count > 0 {
+            println!("  col {}: {count}", x + 1);
+        }
+    }
+    let [left, right] = worlds.sides;
+    if left > 0 || right > 0 {
+        println!("  left side: {left}");
+        println!("  right side: {right}");
+    }
+    if let Ok(total) = worlds.total() {
+        println!("Total: {total}");
+    }
+
+    if let Some(path) = pgm {
+        worlds.write_pgm(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn manifold(s: &str) -> Manifold {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn input() {
+        let manifold = Manifold::new();
+        assert_eq!(manifold.count_splits(), 1711);
+        assert_eq!(manifold.count_worlds(), Ok(36706966158365));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("".parse::<Manifold>().err(), Some(ManifoldError::Empty));
+        assert_eq!(
+            "...\n...".parse::<Manifold>().err(),
+            Some(ManifoldError::MissingStart)
+        );
+        assert_eq!(
+            "S.S\n...".parse::<Manifold>().err(),
+            Some(ManifoldError::MultipleStarts {
+                first: (0, 0),
+                second: (0, 2)
+            })
+        );
+        assert_eq!(
+            ".S.\n.x.".parse::<Manifold>().err(),
+            Some(ManifoldError::UnknownChar { y: 1, x: 1, c: 'x' })
+        );
+        assert_eq!(
+            ".S.\n....".parse::<Manifold>().err(),
+            Some(ManifoldError::RaggedRow {
+                y: 1,
+                expected: 3,
+                found: 4
+            })
+        );
+        assert_eq!(
+            "...\n.S.".parse::<Manifold>().err(),
+            Some(ManifoldError::StartOnLastRow { x: 1 })
+        );
+        assert!(Manifold::try_from(".S.\r\n.^.\r\n...").is_ok());
+    }
+
+    // whatever is right below `S` gets hit by the beam rather than replaced by it
+    #[test]
+    fn thing_under_start() {
+        let splitter = manifold("..S..\n..^..\n.....");
+        assert_eq!(splitter.count_splits(), 1);
+        assert_eq!(splitter.count_worlds(), Ok(2));
+
+        let absorber = manifold(".S.\n.#.\n...");
+        assert_eq!(absorber.count_splits(), 0);
+        assert_eq!(absorber.count_worlds(), Ok(0));
+
+        let triple = manifold("..S..\n..*..\n.^.^.\n.....");
+        assert_eq!(triple.count_splits(), 3);
+        assert_eq!(triple.count_worlds(), Ok(5));
+    }
+
+    // every branch of a splitter on the last row reaches the floor, and the simulation
+    // stops there instead of looking for a row below it
+    #[test]
+    fn last_row_splitters() {
+        let m = manifold("..S..\n.....\n.^^^.");
+        assert_eq!(m.count_splits(), 1);
+        assert_eq!(m.count_worlds(), Ok(2));
+
+        let m = manifold("..S..\n..^..\n.^.^.");
+        assert_eq!(m.count_splits(), 3);
+        assert_eq!(m.count_worlds(), Ok(4));
+    }
+
+    #[test]
+    fn edge_splitters() {
+        // (boundary, splits, worlds) for a splitter against the left wall, on the last row
+        // and in a single column, where both branches leave the shaft
+        let cases = [
+            (Boundary::Absorb, [1, 1, 1], [1, 1, 0]),
+            (Boundary::Reflect, [1, 1, 1], [2, 2, 2]),
+            (Boundary::Wrap, [2, 1, 1], [3, 2, 2]),
+            (Boundary::Floor, [1, 1, 1], [2, 2, 2]),
+        ];
+        for (boundary, splits, worlds) in cases {
+            for (idx, s) in ["S..\n^..\n..^\n...", "S..\n...\n^..", "S\n.\n^"]
+                .iter()
+                .enumerate()
+            {
+                let m = manifold(s).with_boundary(boundary);
+                assert_eq!(m.count_splits(), splits[idx], "{boundary:?} {s:?}");
+                assert_eq!(m.count_worlds(), Ok(worlds[idx]), "{boundary:?} {s:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn trace() {
+        let m = manifold(
+            "...S...\n\
+             .......\n\
+             ^..^...\n\
+             ..^.^..\n\
+             ......^\n\
+             .^...^.\n\
+             .......",
+        );
+        let trace = m.clone().trace();
+
+        let beams: Vec<usize> = trace.ticks.iter().map(|t| t.manifold.beams.len()).collect();
+        let fired: Vec<usize> = trace.ticks.iter().map(|t| t.step.fired.len()).collect();
+        let merged: Vec<usize> = trace.ticks.iter().map(|t| t.step.merged).collect();
+        assert_eq!(beams, [1, 2, 3, 3, 5, 5]);
+        assert_eq!(fired, [0, 1, 2, 0, 2, 0]);
+        // the two splitters hit on tick 2 both send a beam to the middle
+        assert_eq!(merged, [0, 0, 1, 0, 0, 0]);
+        assert_eq!(trace.ticks[2].step.fired, [(3, 2), (3, 4)]);
+
+        assert_eq!(trace.never_hit(), [(2, 0), (4, 6)]);
+        assert_eq!(fired.iter().sum::<usize>(), m.count_splits() as usize);
+    }
+
+    #[test]
+    fn heatmap() {
+        let worlds = manifold("..S..\n..^..\n.^.^.\n.....")
+            .worlds::<u128>()
+            .unwrap();
+        assert_eq!(worlds.heatmap(), "     \n  *  \n * * \n* @ *\n");
+        assert_eq!(worlds.floor, [1, 0, 2, 0, 1]);
+        assert_eq!(worlds.sides, [0, 0]);
+        assert_eq!(worlds.total(), Ok(4));
+
+        // log scale: 1 -> 3/8, 2 -> 4/8, 4 -> 6/8 of the way to the busiest cell
+        let worlds = Worlds {
+            table: vec![vec![0, 1, 2, 4, 8]],
+            floor: vec![],
+            sides: [0, 0],
+        };
+        assert_eq!(worlds.heatmap(), " =+#@\n");
+    }
+
+    #[test]
+    fn floor_sides() {
+        let worlds = manifold("S..\n^..\n...")
+            .with_boundary(Boundary::Floor)
+            .worlds::<u128>()
+            .unwrap();
+        assert_eq!(worlds.floor, [0, 1, 0]);
+        assert_eq!(worlds.sides, [1, 0]);
+        assert_eq!(worlds.total(), Ok(2));
+
+        let worlds = manifold("..S\n..^\n...")
+            .with_boundary(Boundary::Floor)
+            .worlds::<u128>()
+            .unwrap();
+        assert_eq!(worlds.floor, [0, 1, 0]);
+        assert_eq!(worlds.sides, [0, 1]);
+
+        // absorbed timelines end up nowhere
+        let worlds = manifold("S..\n^..\n...")
+            .with_boundary(Boundary::Absorb)
+            .worlds::<u128>()
+            .unwrap();
+        assert_eq!(worlds.floor, [0, 1, 0]);
+        assert_eq!(worlds.sides, [0, 0]);
+        assert_eq!(worlds.total(), Ok(1));
+    }
+
+    #[test]
+    fn pgm() {
+        let worlds = manifold("..S..\n..^..\n.^.^.\n.....")
+            .worlds::<u128>()
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("aoc2025-d7-{}.pgm", std::process::id()));
+        worlds.write_pgm(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let header = b"P5\n5 4\n255\n";
+        assert!(bytes.starts_with(header));
+        assert_eq!(bytes.len(), header.len() + 5 * 4);
+        assert_eq!(
+            bytes[header.len()..],
+            [
+                0, 0, 0, 0, 0, //
+                0, 0, 151, 0, 0, //
+                0, 151, 0, 151, 0, //
+                151, 0, 255, 0, 151,
+            ]
+        );
+    }
+
+    // `S` above `rows - 2` rows of splitters, each row lined up with where the previous one
+    // sends the beams, so every timeline splits on every row: 2^(rows - 2) in total
+    fn triangle(rows: usize) -> Manifold {
+        let levels = rows - 2;
+        let width = 2 * levels + 1;
+        let mut s = format!("{:.^width$}\n{}\n", "S", ".".repeat(width));
+        for level in 0..levels {
+            let row: String = (0..width)
+                .map(|x| {
+                    let offset = x as isize - levels as isize;
+                    let on_level =
+                        offset.unsigned_abs() <= level && (offset + level as isize) % 2 == 0;
+                    if on_level { '^' } else { '.' }
+                })
+                .collect();
+            s.push_str(&row);
+            s.push('\n');
+        }
+        manifold(&s)
+    }
+
+    #[test]
+    fn triangle_counters() {
+        let small = triangle(100);
+        assert_eq!(small.count_worlds(), Ok(1 << 98));
+        assert_eq!(
+            small.count_worlds_as::<BigUint>(),
+            Ok(BigUint::from(2).pow(98))
+        );
+
+        let m = triangle(200);
+        assert_eq!(m.count_splits(), 198 * 199 / 2);
+        assert!(matches!(m.count_worlds(), Err(Overflow::Cell { .. })));
+        assert_eq!(
+            m.count_worlds_as::<BigUint>(),
+            Ok(BigUint::from(2).pow(198))
+        );
+
+        let expected = (0..198).fold(1, |acc, _| acc * 2 % MODULUS);
+        assert_eq!(
+            m.count_worlds_as::<Modular<MODULUS>>(),
+            Ok(Modular(expected))
+        );
+        assert_eq!(m.count_worlds_as::<bool>(), Ok(true));
+    }
+
+    // `S` somewhere in the top row, and each cell below that being something other than air
+    // with a chance of `density`
+    fn random(rng: &mut Rng, width: usize, height: usize, density: f64) -> Manifold {
+        let start = rng.below(width as u64) as usize;
+        let mut s = String::new();
+
+        for y in 0..height.max(2) {
+            for x in 0..width {
+                let c = match y {
+                    0 if x == start => 'S',
+                    0 => '.',
+                    _ if rng.chance(density) => b"^<>#*"[rng.below(5) as usize] as char,
+                    _ => '.',
+                };
+                s.push(c);
+            }
+            s.push('\n');
+        }
+
+        manifold(&s)
+    }
+
+    // The P1 split count has to match the number of splitters that timelines reach in the P2
+    // table, for every boundary policy
+    #[test]
+    fn random_manifolds() {
+        let mut seeds = Rng::new(44);
+
+        for _ in 0..2000 {
+            let seed = seeds.next_u64();
+            let mut rng = Rng::new(seed);
+
+            let width = rng.range(1, 40);
+            let height = rng.range(2, 40);
+            let density = rng.f64();
+            let boundary = match rng.below(4) {
+                0 => Boundary::Absorb,
+                1 => Boundary::Reflect,
+                2 => Boundary::Wrap,
+                _ => Boundary::Floor,
+            };
+            let manifold = random(&mut rng, width, height, density).with_boundary(boundary);
+
+            let reached = manifold.worlds::<bool>().unwrap();
+            let expected = manifold
+                .shaft
+                .iter()
+                .zip(reached.table.iter())
+                .flat_map(|(row, reached)| row.iter().zip(reached.iter()))
+                .filter(|(thing, reached)| thing.is_splitter() && **reached)
+                .count();
+
+            assert_eq!(
+                manifold.count_splits() as usize,
+                expected,
+                "seed {seed}:\n{manifold}"
+            );
+        }
+    }
 }
@@ -1,10 +1,12 @@
 use std::{
-    cell::RefCell,
-    fs::File,
-    io::{BufRead, BufReader},
-    ops::{Deref, DerefMut},
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fs, io,
+    path::Path,
 };
 
+use crate::input;
+
 #[derive(Debug, Hash, Eq, PartialEq, Clone, Copy)]
 struct Junction {
     x: i64,
@@ -12,161 +14,877 @@ struct Junction {
     z: i64,
 }
 
-impl Junction {
-    fn euclidean_distance(&self, other: &Junction) -> i64 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        let dz = other.z - self.z;
-        dx * dx + dy * dy + dz * dz // Note: This should be .sqrt() for actual euclidean_distance
-    }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    SquaredEuclidean,
+    Euclidean,
+    Manhattan,
+    Chebyshev,
 }
 
-#[derive(Debug)]
-struct CircuitVec(Vec<RefCell<Circuit>>);
+impl Metric {
+    // Exact key pairs are ordered by, in i128 so nothing near the i64 range overflows.
+    // Euclidean orders by the squared distance as well, the root is only taken in `value`.
+    fn key(&self, a: &Junction, b: &Junction) -> i128 {
+        let deltas = [
+            b.x as i128 - a.x as i128,
+            b.y as i128 - a.y as i128,
+            b.z as i128 - a.z as i128,
+        ];
+        match self {
+            Metric::SquaredEuclidean | Metric::Euclidean => deltas.iter().map(|d| d * d).sum(),
+            Metric::Manhattan => deltas.iter().map(|d| d.abs()).sum(),
+            Metric::Chebyshev => deltas.iter().map(|d| d.abs()).max().unwrap(),
+        }
+    }
 
-impl CircuitVec {
-    fn connect_junctions(&mut self, a: &Junction, b: &Junction) {
-        let circuit_a = self
-            .0
-            .iter()
-            .find(|circuit| circuit.borrow().junctions.contains(a));
-        let circuit_b = self
-            .0
-            .iter()
-            .find(|circuit| circuit.borrow().junctions.contains(b));
-
-        match (circuit_a, circuit_b) {
-            (None, None) => {
-                let new_circuit = Circuit {
-                    junctions: vec![*a, *b],
-                };
-                self.0.push(new_circuit.into());
-            }
-            (None, Some(circuit_b)) => {
-                circuit_b.borrow_mut().push(*a);
-            }
-            (Some(circuit_a), None) => {
-                circuit_a.borrow_mut().push(*b);
-            }
-            (Some(circuit_a), Some(circuit_b)) => {
-                let circuit_b_idx = self
-                    .0
-                    .iter()
-                    .position(|circuit| circuit.borrow().contains(b))
-                    .unwrap();
-                circuit_a
-                    .borrow_mut()
-                    .extend_from_slice(&circuit_b.borrow());
-                self.0.swap_remove(circuit_b_idx);
-            }
+    // Smallest key possible between two junctions `delta` apart along a single axis
+    fn axis_key(&self, delta: i128) -> i128 {
+        match self {
+            Metric::SquaredEuclidean | Metric::Euclidean => delta * delta,
+            Metric::Manhattan | Metric::Chebyshev => delta.abs(),
+        }
+    }
+
+    // The actual distance a key stands for
+    fn value(&self, key: i128) -> f64 {
+        match self {
+            Metric::Euclidean => (key as f64).sqrt(),
+            _ => key as f64,
         }
     }
 }
 
-#[derive(Debug)]
-struct Circuit {
-    junctions: Vec<Junction>,
+// Junctions connected pair by pair, closest first, keeping track of how many circuits of
+// each size there are so it can be queried after any number of connections
+struct Circuits<'a> {
+    junctions: &'a [Junction],
+    metric: Metric,
+    pairs: SortedPairs<'a>,
+    sets: DisjointSet,
+    // circuit size -> number of circuits that size, unconnected junctions being size 1
+    histogram: BTreeMap<usize, usize>,
+    connections: usize,
 }
 
-impl Deref for Circuit {
-    type Target = Vec<Junction>;
+impl<'a> Circuits<'a> {
+    fn new(junctions: &'a [Junction], metric: Metric) -> Self {
+        Self {
+            junctions,
+            metric,
+            pairs: SortedPairs::new(junctions, metric),
+            sets: DisjointSet::new(junctions.len()),
+            histogram: match junctions.len() {
+                0 => BTreeMap::new(),
+                n => BTreeMap::from([(1, n)]),
+            },
+            connections: 0,
+        }
+    }
+
+    fn remove_size(&mut self, size: usize) {
+        let count = self.histogram.get_mut(&size).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.histogram.remove(&size);
+        }
+    }
+
+    // Connects the next closest pair, returns false once there are no pairs left
+    fn connect_next(&mut self) -> bool {
+        let Some((a, b, _)) = self.pairs.next() else {
+            return false;
+        };
+        self.connections += 1;
+
+        let (size_a, size_b) = (self.sets.size_of(a), self.sets.size_of(b));
+        if self.sets.union(a, b).is_some() {
+            self.remove_size(size_a);
+            self.remove_size(size_b);
+            *self.histogram.entry(size_a + size_b).or_default() += 1;
+        }
+        true
+    }
 
-    fn deref(&self) -> &Self::Target {
-        &self.junctions
+    // Histogram after the `connections` closest pairs have been connected (pairs that are
+    // already in the same circuit count too). Moving forward only makes the missing
+    // connections, going back starts over.
+    fn histogram_after(&mut self, connections: usize) -> &BTreeMap<usize, usize> {
+        if connections < self.connections {
+            *self = Self::new(self.junctions, self.metric);
+        }
+        while self.connections < connections && self.connect_next() {}
+        &self.histogram
+    }
+
+    // Sizes of the `top` largest circuits, largest first
+    fn largest(&self, top: usize) -> Vec<usize> {
+        self.histogram
+            .iter()
+            .rev()
+            .flat_map(|(size, count)| std::iter::repeat_n(*size, *count))
+            .take(top)
+            .collect()
+    }
+
+    // Members of every circuit, each sorted
+    fn members(&mut self) -> Vec<Vec<usize>> {
+        let mut by_root: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for i in 0..self.junctions.len() {
+            by_root.entry(self.sets.find(i)).or_default().push(i);
+        }
+        by_root.into_values().collect()
     }
 }
 
-impl DerefMut for Circuit {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.junctions
+#[derive(Debug, Clone, Copy)]
+pub enum Aggregate {
+    Product,
+    Sum,
+    List,
+}
+
+impl Aggregate {
+    fn apply(&self, sizes: &[usize]) -> String {
+        match self {
+            Aggregate::Product => sizes.iter().product::<usize>().to_string(),
+            Aggregate::Sum => sizes.iter().sum::<usize>().to_string(),
+            Aggregate::List => format!("{sizes:?}"),
+        }
     }
 }
 
-pub fn solve() {
-    let f = File::open("./src/d8/input.txt").unwrap();
-    let reader = BufReader::new(f);
+fn parse() -> Vec<Junction> {
+    parse_junctions(&fs::read_to_string("./src/d8/input.txt").unwrap())
+}
 
-    let junctions: Vec<_> = reader
-        .lines()
+// One `x,y,z` junction per line
+fn parse_junctions(input: &str) -> Vec<Junction> {
+    input::lines(&input::normalize(input))
+        .into_iter()
         .map(|line| {
-            let binding = line.unwrap();
-            let mut parts = binding.splitn(3, ',');
+            let mut parts = line.splitn(3, ',');
 
             if let (Some(x), Some(y), Some(z)) = (parts.next(), parts.next(), parts.next()) {
                 Junction {
-                    x: x.parse().unwrap(),
-                    y: y.parse().unwrap(),
-                    z: z.parse().unwrap(),
+                    x: x.trim().parse().unwrap(),
+                    y: y.trim().parse().unwrap(),
+                    z: z.trim().parse().unwrap(),
                 }
             } else {
                 panic!("sum ting wong")
             }
         })
-        .collect();
+        .collect()
+}
+
+pub fn solve() {
+    let junctions = parse();
 
     // P1
-    let mut circuits = CircuitVec(vec![]);
+    let mut circuits = Circuits::new(&junctions, Metric::SquaredEuclidean);
+    circuits.histogram_after(1000);
+    let result = Aggregate::Product.apply(&circuits.largest(3));
+    println!("P1: {result}");
+
+    // P2
+    // the last merge is the one that connects everything
+    let last = single_linkage(&junctions, Metric::SquaredEuclidean)
+        .last()
+        .copied()
+        .unwrap();
+    let result = junctions[last.a].x * junctions[last.b].x;
+    println!("P2: {result}");
+}
+
+// P1 with its knobs: connect the `connections` closest pairs, then aggregate the sizes of
+// the `top` largest circuits of the junctions in `input`. Every size in `histograms` gets
+// the circuit-size histogram after that many connections printed.
+pub fn solve_circuits(
+    input: &str,
+    metric: Metric,
+    connections: usize,
+    top: usize,
+    aggregate: Aggregate,
+    histograms: &[usize],
+) {
+    let junctions = parse_junctions(input);
+    let mut circuits = Circuits::new(&junctions, metric);
+
+    for &after in histograms {
+        let histogram = circuits.histogram_after(after);
+        let sizes: Vec<String> = histogram
+            .iter()
+            .rev()
+            .map(|(size, count)| format!("{size}x{count}"))
+            .collect();
+        println!("After {after}: {}", sizes.join(" "));
+    }
+
+    circuits.histogram_after(connections);
+    println!("Result: {}", aggregate.apply(&circuits.largest(top)));
+}
 
-    for (a, b, _) in find_and_sort_pairs(&junctions).iter().take(1000) {
-        let ac = circuits.0.iter().find(|c| c.borrow().contains(a));
-        if let Some(ac) = ac
-            && ac.borrow().contains(b)
-        {
-            continue;
+// Connects the `connections` closest pairs of the junctions in `input` under both metrics
+// and prints where the resulting circuits differ
+pub fn compare_metrics(input: &str, a: Metric, b: Metric, connections: usize) {
+    let junctions = parse_junctions(input);
+
+    let diverged = SortedPairs::new(&junctions, a)
+        .zip(SortedPairs::new(&junctions, b))
+        .take(connections)
+        .position(|((a1, a2, _), (b1, b2, _))| (a1, a2) != (b1, b2));
+    match diverged {
+        Some(idx) => println!("Connection {} is the first to differ", idx + 1),
+        None => println!("The first {connections} connections are the same"),
+    }
+
+    let mut circuits_a = Circuits::new(&junctions, a);
+    let mut circuits_b = Circuits::new(&junctions, b);
+    circuits_a.histogram_after(connections);
+    circuits_b.histogram_after(connections);
+    let members_a = circuits_a.members();
+    let members_b = circuits_b.members();
+
+    for (metric, circuits, members, other) in [
+        (a, &circuits_a, &members_a, &members_b),
+        (b, &circuits_b, &members_b, &members_a),
+    ] {
+        let only_here: Vec<&Vec<usize>> = members
+            .iter()
+            .filter(|circuit| circuit.len() > 1 && !other.contains(circuit))
+            .collect();
+        println!(
+            "{metric:?}: largest {:?}, {} circuits not found with the other metric",
+            circuits.largest(3),
+            only_here.len()
+        );
+        for circuit in only_here.iter().take(5) {
+            println!("  {circuit:?}");
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+            size: vec![1; n],
         }
-        circuits.connect_junctions(a, b);
     }
 
-    circuits.0.sort_by_key(|c| c.borrow().len());
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            // path halving
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
 
-    let result: usize = circuits
-        .0
-        .iter()
-        .rev()
-        .take(3)
-        .map(|v| v.borrow().len())
-        .product();
+    fn size_of(&mut self, i: usize) -> usize {
+        let root = self.find(i);
+        self.size[root]
+    }
 
-    println!("P1: {}", result);
+    // Returns the new root, or `None` if `a` and `b` already were in the same set
+    fn union(&mut self, a: usize, b: usize) -> Option<usize> {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return None;
+        }
+        if self.size[a] < self.size[b] {
+            (a, b) = (b, a);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+        Some(a)
+    }
+}
 
-    // P2
-    circuits = CircuitVec(vec![]);
+// One step of single-linkage clustering, i.e. one edge of the minimum spanning tree.
+// Clusters are numbered like scipy does it: 0..n are the junctions themselves, and the
+// cluster created by merge k is n + k.
+#[derive(Debug, Clone, Copy)]
+struct Merge {
+    // the junctions whose connection merged the clusters
+    a: usize,
+    b: usize,
+    distance: f64,
+    left: usize,
+    right: usize,
+    // junctions in the merged cluster
+    size: usize,
+}
 
-    let binding = find_and_sort_pairs(&junctions);
-    let mut pairs_iter = binding.iter();
+// Kruskal's algorithm: connect the closest pairs until everything is one circuit
+fn single_linkage(junctions: &[Junction], metric: Metric) -> Vec<Merge> {
+    let n = junctions.len();
+    let mut sets = DisjointSet::new(n);
+    // cluster id of every set, by root
+    let mut cluster: Vec<usize> = (0..n).collect();
+    let mut merges: Vec<Merge> = Vec::with_capacity(n.saturating_sub(1));
 
-    let result = loop {
-        if let Some((a, b, _)) = pairs_iter.next() {
-            let ac = circuits.0.iter().find(|c| c.borrow().contains(a));
-            if let Some(ac) = ac
-                && ac.borrow().contains(b)
-            {
-                continue;
+    for (a, b, key) in SortedPairs::new(junctions, metric) {
+        if merges.len() + 1 >= n {
+            break;
+        }
+        let (left, right) = (cluster[sets.find(a)], cluster[sets.find(b)]);
+        if let Some(root) = sets.union(a, b) {
+            cluster[root] = n + merges.len();
+            merges.push(Merge {
+                a,
+                b,
+                distance: metric.value(key),
+                left,
+                right,
+                size: sets.size[root],
+            });
+        }
+    }
+
+    merges
+}
+
+fn mst_csv(junctions: &[Junction], merges: &[Merge]) -> String {
+    let mut out = String::from("a,b,ax,ay,az,bx,by,bz,distance\n");
+    for m in merges {
+        let (a, b) = (&junctions[m.a], &junctions[m.b]);
+        out.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            m.a, m.b, a.x, a.y, a.z, b.x, b.y, b.z, m.distance
+        ));
+    }
+    out
+}
+
+// Same layout as a scipy linkage matrix, plus the step
+fn linkage_csv(merges: &[Merge]) -> String {
+    let mut out = String::from("step,left,right,distance,size\n");
+    for (step, m) in merges.iter().enumerate() {
+        out.push_str(&format!(
+            "{step},{},{},{},{}\n",
+            m.left, m.right, m.distance, m.size
+        ));
+    }
+    out
+}
+
+// The dendrogram as Newick text, leaves named `j<index>` and branch lengths being the
+// difference in merge distance. Built without recursion since chains can get very deep.
+fn newick(n: usize, merges: &[Merge]) -> String {
+    let height = |cluster: usize| {
+        if cluster < n {
+            0.0
+        } else {
+            merges[cluster - n].distance
+        }
+    };
+
+    enum Visit {
+        Open(usize, f64),
+        Comma,
+        Close(usize, f64),
+    }
+
+    // a lone junction (or none at all) never gets merged
+    if merges.is_empty() {
+        return (0..n).map(|i| format!("j{i}")).collect::<String>() + ";";
+    }
+    let root = n + merges.len() - 1;
+
+    let mut out = String::new();
+    let mut stack = vec![Visit::Open(root, height(root))];
+    while let Some(visit) = stack.pop() {
+        match visit {
+            Visit::Open(cluster, parent_height) if cluster < n => {
+                out.push_str(&format!("j{cluster}:{}", parent_height - height(cluster)));
+            }
+            Visit::Open(cluster, parent_height) => {
+                let m = &merges[cluster - n];
+                let h = height(cluster);
+                out.push('(');
+                stack.push(Visit::Close(cluster, parent_height));
+                stack.push(Visit::Open(m.right, h));
+                stack.push(Visit::Comma);
+                stack.push(Visit::Open(m.left, h));
             }
-            circuits.connect_junctions(a, b);
-            // break when the first and only circuit contains all junctions
-            if circuits.0.first().unwrap().borrow().len() == junctions.len() {
-                break a.x * b.x;
+            Visit::Comma => out.push(','),
+            Visit::Close(cluster, parent_height) => {
+                out.push(')');
+                if cluster != root {
+                    out.push_str(&format!(":{}", parent_height - height(cluster)));
+                }
             }
         }
-    };
-    println!("P2: {result}");
+    }
+    out.push(';');
+    out
+}
+
+// Writes the minimum spanning tree (`mst.csv`), the single-linkage merges (`linkage.csv`)
+// and the dendrogram (`dendrogram.nwk`) into `dir`
+pub fn export(dir: &Path, metric: Metric) -> io::Result<()> {
+    let junctions = parse();
+    let merges = single_linkage(&junctions, metric);
+
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("mst.csv"), mst_csv(&junctions, &merges))?;
+    fs::write(dir.join("linkage.csv"), linkage_csv(&merges))?;
+    fs::write(dir.join("dendrogram.nwk"), newick(junctions.len(), &merges))?;
+
+    Ok(())
 }
 
-fn find_and_sort_pairs(junctions: &[Junction]) -> Vec<(&Junction, &Junction, i64)> {
-    let mut pairs = Vec::new();
+// Static k-d tree over the junctions, stored as a permutation of their indexes where every
+// subrange has its splitting junction in the middle
+struct KdTree<'a> {
+    junctions: &'a [Junction],
+    metric: Metric,
+    order: Vec<usize>,
+}
 
-    for i in 0..junctions.len() {
-        let a = &junctions[i];
+fn axis(junction: &Junction, depth: usize) -> i64 {
+    match depth % 3 {
+        0 => junction.x,
+        1 => junction.y,
+        _ => junction.z,
+    }
+}
+
+impl<'a> KdTree<'a> {
+    fn new(junctions: &'a [Junction], metric: Metric) -> Self {
+        fn build(order: &mut [usize], junctions: &[Junction], depth: usize) {
+            if order.len() <= 1 {
+                return;
+            }
+            let mid = order.len() / 2;
+            order.select_nth_unstable_by_key(mid, |idx| axis(&junctions[*idx], depth));
+            let (left, right) = order.split_at_mut(mid);
+            build(left, junctions, depth + 1);
+            build(&mut right[1..], junctions, depth + 1);
+        }
+
+        let mut order: Vec<usize> = (0..junctions.len()).collect();
+        build(&mut order, junctions, 0);
+        Self {
+            junctions,
+            metric,
+            order,
+        }
+    }
+
+    // The `k` nearest other junctions to `idx` as (distance, index), ordered by distance and
+    // then index
+    fn nearest(&self, idx: usize, k: usize) -> Vec<(i128, usize)> {
+        let mut best: BinaryHeap<(i128, usize)> = BinaryHeap::new();
+        self.search(idx, k, 0, self.order.len(), 0, &mut best);
+        best.into_sorted_vec()
+    }
+
+    fn search(
+        &self,
+        idx: usize,
+        k: usize,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        best: &mut BinaryHeap<(i128, usize)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let cur = self.order[mid];
+        let target = &self.junctions[idx];
+
+        if cur != idx {
+            let candidate = (self.metric.key(target, &self.junctions[cur]), cur);
+            if best.len() < k {
+                best.push(candidate);
+            } else if candidate < *best.peek().unwrap() {
+                best.pop();
+                best.push(candidate);
+            }
+        }
+
+        let diff = axis(target, depth) as i128 - axis(&self.junctions[cur], depth) as i128;
+        let (near, far) = if diff < 0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+
+        self.search(idx, k, near.0, near.1, depth + 1, best);
+        // the other side can only matter if the splitting plane is close enough, ties
+        // included since they're broken by index
+        if best.len() < k || self.metric.axis_key(diff) <= best.peek().unwrap().0 {
+            self.search(idx, k, far.0, far.1, depth + 1, best);
+        }
+    }
+}
+
+// Yields every pair of junctions ordered by distance, ties in the order the pairs would be
+// listed in (first junction, then second). Each junction walks its own neighbours, nearest
+// first, and a heap picks whichever pair is next overall, so only the pairs actually used
+// get looked at. Every pair shows up once from both ends, right after each other.
+struct SortedPairs<'a> {
+    tree: KdTree<'a>,
+    // neighbours found so far per junction, and how many of them were used
+    neighbours: Vec<Vec<(i128, usize)>>,
+    used: Vec<usize>,
+    // (distance, lower index, higher index, junction whose neighbour this is)
+    heap: BinaryHeap<Reverse<(i128, usize, usize, usize)>>,
+    last: Option<(i128, usize, usize)>,
+}
 
-        (i + 1..junctions.len()).for_each(|j| {
-            let b = &junctions[j];
-            let d = a.euclidean_distance(b);
+const FIRST_NEIGHBOURS: usize = 4;
+
+impl<'a> SortedPairs<'a> {
+    fn new(junctions: &'a [Junction], metric: Metric) -> Self {
+        let mut pairs = Self {
+            tree: KdTree::new(junctions, metric),
+            neighbours: vec![vec![]; junctions.len()],
+            used: vec![0; junctions.len()],
+            heap: BinaryHeap::new(),
+            last: None,
+        };
+        for i in 0..junctions.len() {
+            pairs.queue_next(i);
+        }
+        pairs
+    }
+
+    // Queues the nearest neighbour of `i` that wasn't queued yet, fetching twice as many
+    // neighbours whenever they run out
+    fn queue_next(&mut self, i: usize) {
+        if self.used[i] == self.neighbours[i].len() {
+            let found = self.neighbours[i].len();
+            if found + 1 >= self.tree.junctions.len() {
+                return;
+            }
+            let k = (found * 2).max(FIRST_NEIGHBOURS);
+            self.neighbours[i] = self.tree.nearest(i, k);
+        }
+
+        let (d, j) = self.neighbours[i][self.used[i]];
+        self.used[i] += 1;
+        self.heap.push(Reverse((d, i.min(j), i.max(j), i)));
+    }
+}
+
+// Yields (first index, second index, metric key)
+impl Iterator for SortedPairs<'_> {
+    type Item = (usize, usize, i128);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Reverse((d, i, j, owner)) = self.heap.pop()?;
+            self.queue_next(owner);
+
+            // the same pair, coming from the other junction
+            if self.last == Some((d, i, j)) {
+                continue;
+            }
+            self.last = Some((d, i, j));
+
+            return Some((i, j, d));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    const METRICS: [Metric; 4] = [
+        Metric::SquaredEuclidean,
+        Metric::Euclidean,
+        Metric::Manhattan,
+        Metric::Chebyshev,
+    ];
+
+    // The original approach: every pair, stable sorted by distance
+    fn all_pairs(junctions: &[Junction], metric: Metric) -> Vec<(usize, usize, i128)> {
+        let mut pairs = vec![];
+        for i in 0..junctions.len() {
+            for j in i + 1..junctions.len() {
+                pairs.push((i, j, metric.key(&junctions[i], &junctions[j])));
+            }
+        }
+        pairs.sort_by_key(|(_, _, d)| *d);
+        pairs
+    }
 
-            pairs.push((a, b, d));
-        });
+    // Coordinates from a tiny range, so there are lots of equal distances and duplicates
+    fn random_junctions(rng: &mut Rng) -> Vec<Junction> {
+        let n = rng.range(0, 30);
+        let max = rng.range(0, 4) as u64 + 1;
+        (0..n)
+            .map(|_| Junction {
+                x: rng.below(max) as i64,
+                y: rng.below(max) as i64,
+                z: rng.below(max) as i64,
+            })
+            .collect()
     }
 
-    pairs.sort_by_key(|(_, _, d)| *d);
-    pairs
+    #[test]
+    fn sorted_pairs_match_all_pairs() {
+        let mut rng = Rng::new(45);
+        for _ in 0..500 {
+            let junctions = random_junctions(&mut rng);
+            for metric in METRICS {
+                let pairs: Vec<_> = SortedPairs::new(&junctions, metric).collect();
+                assert_eq!(
+                    pairs,
+                    all_pairs(&junctions, metric),
+                    "{metric:?} {junctions:?}"
+                );
+            }
+        }
+    }
+
+    // j1 is 3 away from j0, j2 is 4 away from j0 and 5 from j1, j3 is 7 away from j1
+    fn fixture() -> Vec<Junction> {
+        [(0, 0, 0), (3, 0, 0), (0, 4, 0), (10, 0, 0)]
+            .map(|(x, y, z)| Junction { x, y, z })
+            .to_vec()
+    }
+
+    #[test]
+    fn single_linkage_fixture() {
+        let junctions = fixture();
+        let merges = single_linkage(&junctions, Metric::Euclidean);
+        let steps: Vec<_> = merges
+            .iter()
+            .map(|m| (m.a, m.b, m.distance, m.left, m.right, m.size))
+            .collect();
+        // merge k creates cluster 4 + k
+        assert_eq!(
+            steps,
+            [
+                (0, 1, 3.0, 0, 1, 2),
+                (0, 2, 4.0, 4, 2, 3),
+                (1, 3, 7.0, 5, 3, 4)
+            ]
+        );
+
+        assert_eq!(
+            mst_csv(&junctions, &merges),
+            "a,b,ax,ay,az,bx,by,bz,distance\n\
+             0,1,0,0,0,3,0,0,3\n\
+             0,2,0,0,0,0,4,0,4\n\
+             1,3,3,0,0,10,0,0,7\n"
+        );
+        assert_eq!(
+            linkage_csv(&merges),
+            "step,left,right,distance,size\n\
+             0,0,1,3,2\n\
+             1,4,2,4,3\n\
+             2,5,3,7,4\n"
+        );
+        assert_eq!(
+            newick(junctions.len(), &merges),
+            "(((j0:3,j1:3):1,j2:4):3,j3:7);"
+        );
+    }
+
+    #[test]
+    fn single_linkage_degenerate() {
+        let junctions = fixture()[..1].to_vec();
+        let merges = single_linkage(&junctions, Metric::Euclidean);
+        assert!(merges.is_empty());
+        assert_eq!(
+            mst_csv(&junctions, &merges),
+            "a,b,ax,ay,az,bx,by,bz,distance\n"
+        );
+        assert_eq!(linkage_csv(&merges), "step,left,right,distance,size\n");
+        assert_eq!(newick(1, &merges), "j0;");
+
+        assert!(single_linkage(&[], Metric::Euclidean).is_empty());
+        assert_eq!(newick(0, &[]), ";");
+    }
+
+    // Sizes after connecting the first `connections` of `all_pairs`, by relabelling
+    fn naive_histogram(
+        junctions: &[Junction],
+        metric: Metric,
+        connections: usize,
+    ) -> BTreeMap<usize, usize> {
+        let mut label: Vec<usize> = (0..junctions.len()).collect();
+        for (a, b, _) in all_pairs(junctions, metric).into_iter().take(connections) {
+            let (from, to) = (label[b], label[a]);
+            label
+                .iter_mut()
+                .filter(|l| **l == from)
+                .for_each(|l| *l = to);
+        }
+        let mut sizes: BTreeMap<usize, usize> = BTreeMap::new();
+        for l in &label {
+            *sizes.entry(*l).or_default() += 1;
+        }
+        let mut histogram = BTreeMap::new();
+        for size in sizes.values() {
+            *histogram.entry(*size).or_default() += 1;
+        }
+        histogram
+    }
+
+    #[test]
+    fn histogram_after_fixture() {
+        let junctions = fixture();
+        let mut circuits = Circuits::new(&junctions, Metric::SquaredEuclidean);
+        let expected = [
+            vec![(1, 4)],
+            vec![(1, 2), (2, 1)],
+            vec![(1, 1), (3, 1)],
+            // 1-2 are already connected through 0
+            vec![(1, 1), (3, 1)],
+            vec![(4, 1)],
+        ];
+        for (connections, expected) in expected.iter().enumerate() {
+            let histogram = circuits.histogram_after(connections);
+            assert_eq!(*histogram, BTreeMap::from_iter(expected.clone()));
+        }
+        // more connections than pairs just runs out
+        assert_eq!(*circuits.histogram_after(100), BTreeMap::from([(4, 1)]));
+        assert_eq!(circuits.connections, 6);
+        assert_eq!(circuits.largest(3), [4]);
+
+        // going back starts over
+        let histogram = circuits.histogram_after(1);
+        assert_eq!(*histogram, BTreeMap::from([(1, 2), (2, 1)]));
+        assert_eq!(circuits.connections, 1);
+        assert_eq!(circuits.largest(3), [2, 1, 1]);
+    }
+
+    #[test]
+    fn histogram_after_matches_naive() {
+        let mut rng = Rng::new(47);
+        for _ in 0..200 {
+            let junctions = random_junctions(&mut rng);
+            let pairs = junctions.len() * junctions.len().saturating_sub(1) / 2;
+            for metric in METRICS {
+                let mut circuits = Circuits::new(&junctions, metric);
+                for _ in 0..5 {
+                    let connections = rng.range(0, pairs + 1);
+                    assert_eq!(
+                        *circuits.histogram_after(connections),
+                        naive_histogram(&junctions, metric, connections),
+                        "{metric:?} {junctions:?} after {connections}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_junctions_input() {
+        let junctions = parse_junctions("162,817,812\r\n57,618, 57\n\n");
+        assert_eq!(
+            junctions,
+            [
+                Junction {
+                    x: 162,
+                    y: 817,
+                    z: 812
+                },
+                Junction {
+                    x: 57,
+                    y: 618,
+                    z: 57
+                },
+            ]
+        );
+    }
+
+    fn junctions(points: &[(i64, i64, i64)]) -> Vec<Junction> {
+        points
+            .iter()
+            .map(|&(x, y, z)| Junction { x, y, z })
+            .collect()
+    }
+
+    fn circuits_after(
+        junctions: &[Junction],
+        metric: Metric,
+        connections: usize,
+    ) -> Vec<Vec<usize>> {
+        let mut circuits = Circuits::new(junctions, metric);
+        circuits.histogram_after(connections);
+        circuits.members()
+    }
+
+    // 0 and 1 are closer as the crow flies, 2 and 3 when moving along the axes
+    #[test]
+    fn manhattan_differs_from_euclidean() {
+        let junctions = junctions(&[(0, 0, 0), (5, 5, 0), (100, 0, 0), (108, 0, 0)]);
+
+        let euclidean = circuits_after(&junctions, Metric::Euclidean, 1);
+        assert_eq!(euclidean, vec![vec![0, 1], vec![2], vec![3]]);
+        assert_eq!(
+            circuits_after(&junctions, Metric::SquaredEuclidean, 1),
+            euclidean
+        );
+        assert_eq!(circuits_after(&junctions, Metric::Chebyshev, 1), euclidean);
+
+        let manhattan = circuits_after(&junctions, Metric::Manhattan, 1);
+        assert_eq!(manhattan, vec![vec![0], vec![1], vec![2, 3]]);
+
+        // once the second pair is connected too they agree again
+        assert_eq!(
+            circuits_after(&junctions, Metric::Euclidean, 2),
+            circuits_after(&junctions, Metric::Manhattan, 2)
+        );
+    }
+
+    #[test]
+    fn metric_values() {
+        let [a, b] = junctions(&[(1, 2, 3), (4, 6, 15)])[..] else {
+            unreachable!()
+        };
+        assert_eq!(Metric::SquaredEuclidean.key(&a, &b), 169);
+        assert_eq!(Metric::Euclidean.value(Metric::Euclidean.key(&a, &b)), 13.0);
+        assert_eq!(Metric::Manhattan.key(&a, &b), 19);
+        assert_eq!(Metric::Chebyshev.key(&a, &b), 12);
+    }
+
+    // the squared distance is well past i64 here
+    #[test]
+    fn large_coordinates() {
+        let far = 3_000_000_000;
+        let junctions = junctions(&[(-far, -far, -far), (far, far, far), (far, far, far - 1)]);
+
+        let pairs: Vec<_> = SortedPairs::new(&junctions, Metric::SquaredEuclidean).collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (1, 2, 1),
+                (0, 2, 12 * far as i128 * far as i128 - 4 * far as i128 + 1),
+                (0, 1, 12 * far as i128 * far as i128),
+            ]
+        );
+    }
+
+    #[test]
+    fn input() {
+        let junctions = parse();
+        let mut circuits = Circuits::new(&junctions, Metric::SquaredEuclidean);
+        circuits.histogram_after(1000);
+        assert_eq!(Aggregate::Product.apply(&circuits.largest(3)), "54600");
+
+        let last = single_linkage(&junctions, Metric::SquaredEuclidean)
+            .last()
+            .copied()
+            .unwrap();
+        assert_eq!(junctions[last.a].x * junctions[last.b].x, 107256172);
+    }
 }
@@ -91,56 +91,60 @@ impl Point {
     }
 }
 
-impl<'a> Tiles<'a> {
-    fn point_in_polygon(&self, point: &Point) -> bool {
-        fn point_on_segment(p: &Point, a: &Point, b: &Point) -> bool {
-            let cross = (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
-
-            if cross != 0 {
-                return false;
-            }
-
-            let min_x = a.x.min(b.x);
-            let max_x = a.x.max(b.x);
-            let min_y = a.y.min(b.y);
-            let max_y = a.y.max(b.y);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    Inside,
+    Outside,
+    Boundary,
+}
 
-            p.x >= min_x && p.x <= max_x && p.y >= min_y && p.y <= max_y
+impl<'a> Tiles<'a> {
+    // Winding number test, exact at any coordinates since it only uses integer cross products
+    fn locate(&self, point: &Point) -> Location {
+        // > 0 when `p` is left of the line through a and b, 0 when it's on it
+        fn cross(a: &Point, b: &Point, p: &Point) -> i128 {
+            (b.x as i128 - a.x as i128) * (p.y as i128 - a.y as i128)
+                - (b.y as i128 - a.y as i128) * (p.x as i128 - a.x as i128)
         }
 
         let n = self.len();
         if n < 3 {
-            return false;
+            return Location::Outside;
         }
 
-        let mut inside = false;
+        let mut winding = 0;
         let mut j = n - 1;
 
         for i in 0..n {
-            let pi = &self[i].point;
-            let pj = &self[j].point;
-
-            // treat edge as inside
-            if point_on_segment(point, pi, pj) {
-                return true;
+            let a = &self[j].point;
+            let b = &self[i].point;
+            let side = cross(a, b, point);
+
+            let on_segment = side == 0
+                && (a.x.min(b.x)..=a.x.max(b.x)).contains(&point.x)
+                && (a.y.min(b.y)..=a.y.max(b.y)).contains(&point.y);
+            if on_segment {
+                return Location::Boundary;
             }
 
-            // ray-casting
-            let intersects = (pi.y > point.y) != (pj.y > point.y);
-            if intersects {
-                let x_intersect = pi.x as f64
-                    + (point.y as f64 - pi.y as f64) * (pj.x as f64 - pi.x as f64)
-                        / (pj.y as f64 - pi.y as f64);
-
-                if (point.x as f64) < x_intersect {
-                    inside = !inside;
+            // edges crossing the horizontal line through the point, upwards when the point
+            // is on their left and downwards when it's on their right
+            if a.y <= point.y {
+                if b.y > point.y && side > 0 {
+                    winding += 1;
                 }
+            } else if b.y <= point.y && side < 0 {
+                winding -= 1;
             }
 
             j = i;
         }
 
-        inside
+        if winding == 0 {
+            Location::Outside
+        } else {
+            Location::Inside
+        }
     }
 
     fn fill_between(&mut self, a: &'a Point, b: &'a Point) {
@@ -162,6 +166,131 @@ impl<'a> Tiles<'a> {
     }
 }
 
+// The polygon squashed onto the distinct coordinates of its corners. Even indexes are the
+// lines through those coordinates and odd ones the open stretches between two neighbouring
+// lines, so each cell is either entirely inside the polygon or entirely outside.
+struct Compressed {
+    xs: Vec<i64>,
+    ys: Vec<i64>,
+    // prefix sums of outside cells, `outside[y][x]` covering every cell above and left of it
+    outside: Vec<Vec<u32>>,
+}
+
+impl Compressed {
+    fn new(tiles: &Tiles, points: &[Point]) -> Self {
+        let distinct = |coord: fn(&Point) -> i64| {
+            let mut values: Vec<i64> = points.iter().map(coord).collect();
+            values.sort_unstable();
+            values.dedup();
+            values
+        };
+        let xs = distinct(|p| p.x);
+        let ys = distinct(|p| p.y);
+
+        // a lattice point standing in for a cell, `None` when the stretch has no tiles at all
+        fn representative(values: &[i64], idx: usize) -> Option<i64> {
+            if idx.is_multiple_of(2) {
+                return Some(values[idx / 2]);
+            }
+            let v = values[idx / 2] + 1;
+            (v < values[idx / 2 + 1]).then_some(v)
+        }
+
+        let cell = |values: &[i64], v: i64| values.binary_search(&v).unwrap() * 2;
+        let width = xs.len() * 2 - 1;
+        let height = ys.len() * 2 - 1;
+
+        // the edges are inside, everything between them is split into regions that get
+        // classified with a single point each
+        let mut inside: Vec<Vec<Option<bool>>> = vec![vec![None; width]; height];
+        for (i, a) in points.iter().enumerate() {
+            let b = &points[(i + 1) % points.len()];
+            let (x1, x2) = (cell(&xs, a.x.min(b.x)), cell(&xs, a.x.max(b.x)));
+            let (y1, y2) = (cell(&ys, a.y.min(b.y)), cell(&ys, a.y.max(b.y)));
+            for row in inside.iter_mut().take(y2 + 1).skip(y1) {
+                row[x1..=x2].fill(Some(true));
+            }
+        }
+
+        for cy in 0..height {
+            for cx in 0..width {
+                if inside[cy][cx].is_some() {
+                    continue;
+                }
+                let (Some(x), Some(y)) = (representative(&xs, cx), representative(&ys, cy)) else {
+                    continue;
+                };
+                let is_inside = tiles.locate(&(x, y).into()) != Location::Outside;
+
+                let mut stack = vec![(cy, cx)];
+                inside[cy][cx] = Some(is_inside);
+                while let Some((y, x)) = stack.pop() {
+                    let neighbours = [
+                        (y.wrapping_sub(1), x),
+                        (y + 1, x),
+                        (y, x.wrapping_sub(1)),
+                        (y, x + 1),
+                    ];
+                    for (ny, nx) in neighbours {
+                        if ny < height && nx < width && inside[ny][nx].is_none() {
+                            inside[ny][nx] = Some(is_inside);
+                            stack.push((ny, nx));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut outside = vec![vec![0; width + 1]; height + 1];
+        for cy in 0..height {
+            for cx in 0..width {
+                // stretches between lines 1 apart have no tiles, so they can't be outside
+                let has_tiles =
+                    representative(&xs, cx).is_some() && representative(&ys, cy).is_some();
+                let is_outside = has_tiles && inside[cy][cx] == Some(false);
+                outside[cy + 1][cx + 1] =
+                    is_outside as u32 + outside[cy][cx + 1] + outside[cy + 1][cx] - outside[cy][cx];
+            }
+        }
+
+        Self { xs, ys, outside }
+    }
+
+    // Whether every tile in the rectangle with corners `a` and `b` is red or green, both
+    // being corners of the polygon
+    fn is_inside(&self, a: &Point, b: &Point) -> bool {
+        let cell = |values: &[i64], v: i64| values.binary_search(&v).unwrap() * 2;
+        let (x1, x2) = (
+            cell(&self.xs, a.x.min(b.x)),
+            cell(&self.xs, a.x.max(b.x)) + 1,
+        );
+        let (y1, y2) = (
+            cell(&self.ys, a.y.min(b.y)),
+            cell(&self.ys, a.y.max(b.y)) + 1,
+        );
+
+        self.outside[y2][x2] + self.outside[y1][x1] == self.outside[y1][x2] + self.outside[y2][x1]
+    }
+}
+
+// Area of the biggest rectangle with two red corners and only red or green tiles inside.
+// Only rectangles that beat the best so far need checking, and each check is O(1).
+fn biggest_inside(tiles: &Tiles, points: &[Point]) -> i64 {
+    let grid = Compressed::new(tiles, points);
+    let mut biggest: i64 = 0;
+    for i in 0..points.len() {
+        let a = points.get(i).unwrap();
+        for j in i + 1..points.len() {
+            let b = points.get(j).unwrap();
+            let area = a.square_area(b);
+            if area > biggest && grid.is_inside(a, b) {
+                biggest = area;
+            }
+        }
+    }
+    biggest
+}
+
 pub fn solve() {
     let f = File::open("./src/d9/input.txt").unwrap();
     let reader = BufReader::new(f);
@@ -211,64 +340,310 @@ pub fn solve() {
     }
     // println!("{}", tiles);
 
-    // 1. sort all pairs of coords in order of biggest area
-    let mut pairs = vec![];
+    let biggest = biggest_inside(&tiles, &points);
+    println!("P2: {biggest}");
+}
 
-    for i in 0..points.len() {
-        let a = points.get(i).unwrap();
-        for j in i + 1..points.len() {
-            let b = points.get(j).unwrap();
-            let area = a.square_area(b);
-            pairs.push((a, b, area));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use std::collections::HashSet;
+
+    // A random simple rectilinear polygon, listed corner by corner: a blob of cells grown
+    // inside a `size` x `size` grid with its holes filled in, traced along its outline.
+    // `None` when the blob has two cells touching only at a corner, which would pinch the
+    // outline.
+    fn random_polygon(rng: &mut Rng, size: usize) -> Option<Vec<Point>> {
+        // one cell of padding all around, so the outline never touches the edge of the grid
+        let padded = size + 2;
+        let mut blob = vec![vec![false; padded]; padded];
+        let mut cells = vec![(padded / 2, padded / 2)];
+        blob[padded / 2][padded / 2] = true;
+
+        let target = rng.range(1, (size * size / 2).max(1));
+        for _ in 0..target * 8 {
+            if cells.len() >= target {
+                break;
+            }
+            let (x, y) = cells[rng.below(cells.len() as u64) as usize];
+            let (x, y) = match rng.below(4) {
+                0 => (x - 1, y),
+                1 => (x + 1, y),
+                2 => (x, y - 1),
+                _ => (x, y + 1),
+            };
+            if (1..=size).contains(&x) && (1..=size).contains(&y) && !blob[y][x] {
+                blob[y][x] = true;
+                cells.push((x, y));
+            }
         }
-    }
-    pairs.sort_by_key(|(_, _, area)| *area);
-
-    // 2. find the first one that has all points inside the polygon
-    let biggest = pairs
-        .iter()
-        .rev()
-        .find(|(a, b, _)| {
-            let min_x = a.x.min(b.x);
-            let max_x = a.x.max(b.x);
-
-            let min_y = a.y.min(b.y);
-            let max_y = a.y.max(b.y);
-
-            // check each corner
-            let corners: &[Point] = &[
-                (min_x, min_y).into(),
-                (min_x, max_y).into(),
-                (max_x, min_y).into(),
-                (max_x, max_y).into(),
+
+        // anything the outside can't reach is a hole
+        let mut outside = vec![vec![false; padded]; padded];
+        let mut stack: Vec<(usize, usize)> = vec![(0, 0)];
+        outside[0][0] = true;
+        while let Some((x, y)) = stack.pop() {
+            let neighbours = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
             ];
-            for corner in corners.iter() {
-                if !tiles.point_in_polygon(corner) {
-                    return false;
+            for (nx, ny) in neighbours {
+                if nx < padded && ny < padded && !blob[ny][nx] && !outside[ny][nx] {
+                    outside[ny][nx] = true;
+                    stack.push((nx, ny));
                 }
             }
-            // check bottom/top edges
-            for x in min_x..=max_x {
-                if !tiles.point_in_polygon(&(x, min_y).into()) {
-                    return false;
+        }
+        for y in 0..padded {
+            for x in 0..padded {
+                blob[y][x] = !outside[y][x];
+            }
+        }
+
+        for y in 0..padded - 1 {
+            for x in 0..padded - 1 {
+                let (a, b) = (blob[y][x], blob[y][x + 1]);
+                let (c, d) = (blob[y + 1][x], blob[y + 1][x + 1]);
+                if a == d && b == c && a != b {
+                    return None;
+                }
+            }
+        }
+
+        // unit edges of the outline, going around the blob with the inside on the left
+        let mut next: HashMap<(i64, i64), (i64, i64)> = HashMap::new();
+        for y in 1..=size {
+            for x in 1..=size {
+                if !blob[y][x] {
+                    continue;
+                }
+                let (px, py) = (x as i64, y as i64);
+                if !blob[y - 1][x] {
+                    next.insert((px, py), (px + 1, py));
+                }
+                if !blob[y][x + 1] {
+                    next.insert((px + 1, py), (px + 1, py + 1));
+                }
+                if !blob[y + 1][x] {
+                    next.insert((px + 1, py + 1), (px, py + 1));
                 }
-                if !tiles.point_in_polygon(&(x, max_y).into()) {
-                    return false;
+                if !blob[y][x - 1] {
+                    next.insert((px, py + 1), (px, py));
                 }
             }
-            // check left/right edges
-            for y in min_y..=max_y {
-                if !tiles.point_in_polygon(&(min_x, y).into()) {
-                    return false;
+        }
+
+        let start = *next.keys().min().unwrap();
+        let mut outline = vec![start];
+        let mut cur = next[&start];
+        while cur != start {
+            outline.push(cur);
+            cur = next[&cur];
+        }
+
+        // only keep the corners
+        let n = outline.len();
+        let mut corners: Vec<Point> = (0..n)
+            .filter(|i| {
+                let (prev, cur, next) =
+                    (outline[(i + n - 1) % n], outline[*i], outline[(i + 1) % n]);
+                (cur.0 - prev.0, cur.1 - prev.1) != (next.0 - cur.0, next.1 - cur.1)
+            })
+            .map(|i| outline[i].into())
+            .collect();
+
+        if rng.chance(0.5) {
+            corners.reverse();
+        }
+        let shift = rng.below(corners.len() as u64) as usize;
+        corners.rotate_left(shift);
+
+        Some(corners)
+    }
+
+    // Where every lattice point in `min..=max` is, by marking the outline tile by tile and
+    // flooding the rest from a corner that's known to be outside. The flood runs at double
+    // resolution, otherwise it couldn't squeeze through a one tile wide gap between two
+    // edges.
+    fn rasterize(polygon: &[Point], min: Point, max: Point) -> HashMap<Point, Location> {
+        let double = |p: &Point| -> Point { (2 * (p.x - min.x), 2 * (p.y - min.y)).into() };
+        let (max_x, max_y) = (2 * (max.x - min.x), 2 * (max.y - min.y));
+
+        let mut boundary: HashSet<Point> = HashSet::new();
+        for (i, a) in polygon.iter().enumerate() {
+            let (a, b) = (double(a), double(&polygon[(i + 1) % polygon.len()]));
+            for x in a.x.min(b.x)..=a.x.max(b.x) {
+                for y in a.y.min(b.y)..=a.y.max(b.y) {
+                    boundary.insert((x, y).into());
                 }
-                if !tiles.point_in_polygon(&(max_x, y).into()) {
-                    return false;
+            }
+        }
+
+        let mut outside: HashSet<Point> = HashSet::from([(0, 0).into()]);
+        let mut stack: Vec<Point> = vec![(0, 0).into()];
+        while let Some(p) = stack.pop() {
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let n: Point = (p.x + dx, p.y + dy).into();
+                let in_range = (0..=max_x).contains(&n.x) && (0..=max_y).contains(&n.y);
+                if in_range && !boundary.contains(&n) && outside.insert(n) {
+                    stack.push(n);
                 }
             }
+        }
 
-            true
-        })
-        .unwrap();
+        let mut locations = HashMap::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                let p: Point = (x, y).into();
+                let location = if boundary.contains(&double(&p)) {
+                    Location::Boundary
+                } else if outside.contains(&double(&p)) {
+                    Location::Outside
+                } else {
+                    Location::Inside
+                };
+                locations.insert(p, location);
+            }
+        }
+
+        locations
+    }
+
+    fn tiles(points: &[Point]) -> Tiles<'_> {
+        Tiles {
+            tiles: points
+                .iter()
+                .map(|p| Tile {
+                    color: Color::Red,
+                    point: Cow::Borrowed(p),
+                })
+                .collect(),
+        }
+    }
+
+    // Every pair of corners, checking every tile of the rectangle
+    fn biggest_brute_force(tiles: &Tiles, points: &[Point]) -> i64 {
+        let mut biggest = 0;
+        for (i, a) in points.iter().enumerate() {
+            for b in &points[i + 1..] {
+                let inside = (a.x.min(b.x)..=a.x.max(b.x)).all(|x| {
+                    (a.y.min(b.y)..=a.y.max(b.y))
+                        .all(|y| tiles.locate(&(x, y).into()) != Location::Outside)
+                });
+                if inside {
+                    biggest = biggest.max(a.square_area(b));
+                }
+            }
+        }
+        biggest
+    }
 
-    println!("P2: {:?}", biggest.2);
+    #[test]
+    fn input() {
+        let points: Vec<Point> = include_str!("./input.txt")
+            .lines()
+            .map(|line| {
+                let (x, y) = line.split_once(',').unwrap();
+                (x.parse().unwrap(), y.parse().unwrap()).into()
+            })
+            .collect();
+        assert_eq!(biggest_inside(&tiles(&points), &points), 1574684850);
+    }
+
+    // two edges right next to each other leave no tiles between them
+    #[test]
+    fn edges_one_apart() {
+        // the gap between x = 1 and x = 2 above the base has no tiles, but it's connected to
+        // the outside above the right block, which does. The whole 6x3 box from (0, 0) to
+        // (5, 2) is still red or green.
+        let points: Vec<Point> = [
+            (0, 0),
+            (5, 0),
+            (5, 2),
+            (2, 2),
+            (2, 1),
+            (1, 1),
+            (1, 3),
+            (0, 3),
+        ]
+        .into_iter()
+        .map(Point::from)
+        .collect();
+        assert_eq!(biggest_inside(&tiles(&points), &points), 18);
+        assert_eq!(biggest_brute_force(&tiles(&points), &points), 18);
+    }
+
+    #[test]
+    fn random_polygons() {
+        let mut seeds = Rng::new(49);
+        for _ in 0..400 {
+            let seed = seeds.next_u64();
+            let mut rng = Rng::new(seed);
+            let size = rng.range(2, 12);
+            let Some(points) = random_polygon(&mut rng, size) else {
+                continue;
+            };
+            let tiles = tiles(&points);
+            assert_eq!(
+                biggest_inside(&tiles, &points),
+                biggest_brute_force(&tiles, &points),
+                "seed {seed}: {points:?}"
+            );
+        }
+    }
+
+    // `Tiles::locate` against a rasterized fill for every lattice point around random small
+    // polygons, shifted far out where floats can't tell neighbouring points apart
+    #[test]
+    fn locate_matches_raster() {
+        let mut seeds = Rng::new(50);
+
+        for _ in 0..1000 {
+            let seed = seeds.next_u64();
+            let mut rng = Rng::new(seed);
+
+            let size = rng.range(1, 12);
+            let polygon = loop {
+                if let Some(polygon) = random_polygon(&mut rng, size) {
+                    break polygon;
+                }
+            };
+            let offset = rng.below(1 << 62) as i64 - (1 << 61);
+            let polygon: Vec<Point> = polygon
+                .iter()
+                .map(|p| (p.x + offset, p.y + offset).into())
+                .collect();
+            let tiles = tiles(&polygon);
+
+            // the padding cell on each side is outside
+            let min: Point = (offset, offset).into();
+            let max: Point = (offset + size as i64 + 2, offset + size as i64 + 2).into();
+            for (p, location) in rasterize(&polygon, min, max) {
+                assert_eq!(
+                    tiles.locate(&p),
+                    location,
+                    "seed {seed}, {p:?} in {polygon:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn locate_corners_and_edges() {
+        let points: Vec<Point> = [(0, 0), (4, 0), (4, 4), (0, 4)]
+            .into_iter()
+            .map(Point::from)
+            .collect();
+        let tiles = tiles(&points);
+        assert_eq!(tiles.locate(&(0, 0).into()), Location::Boundary);
+        assert_eq!(tiles.locate(&(2, 4).into()), Location::Boundary);
+        assert_eq!(tiles.locate(&(2, 2).into()), Location::Inside);
+        assert_eq!(tiles.locate(&(5, 2).into()), Location::Outside);
+        // on the line through an edge, but past its end
+        assert_eq!(tiles.locate(&(6, 0).into()), Location::Outside);
+        assert_eq!(tiles.locate(&(-1, 4).into()), Location::Outside);
+    }
 }
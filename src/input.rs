@@ -0,0 +1,120 @@
+use std::borrow::Cow;
+
+// Turns CRLF (and lone CR) line endings into LF, borrowing when there's nothing to change
+pub fn normalize(input: &str) -> Cow<'_, str> {
+    if !input.contains('\r') {
+        return Cow::Borrowed(input);
+    }
+    Cow::Owned(input.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+// Lines without trailing whitespace. A missing final newline makes no difference, and blank
+// lines at the end are dropped.
+pub fn lines(input: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = input.split('\n').map(str::trim_end).collect();
+    while lines.last().is_some_and(|line| line.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+// Groups of lines separated by one or more blank lines
+pub fn sections(input: &str) -> Vec<Vec<&str>> {
+    let mut sections = vec![];
+    let mut cur = vec![];
+
+    for line in lines(input) {
+        if line.is_empty() {
+            if !cur.is_empty() {
+                sections.push(std::mem::take(&mut cur));
+            }
+        } else {
+            cur.push(line);
+        }
+    }
+    if !cur.is_empty() {
+        sections.push(cur);
+    }
+
+    sections
+}
+
+// Lines right-padded with spaces to the widest one, for inputs where byte columns matter
+pub fn padded_lines(input: &str) -> Vec<String> {
+    let lines = lines(input);
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    lines.iter().map(|line| format!("{line:<width$}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The same text with LF, CRLF and each of those without the final newline
+    fn variants(text: &str) -> Vec<String> {
+        let crlf = text.replace('\n', "\r\n");
+        vec![
+            text.to_string(),
+            text.trim_end_matches('\n').to_string(),
+            crlf.clone(),
+            crlf.trim_end_matches("\r\n").to_string(),
+        ]
+    }
+
+    #[test]
+    fn normalize_line_endings() {
+        assert!(matches!(normalize("a\nb\n"), Cow::Borrowed("a\nb\n")));
+        assert_eq!(normalize("a\r\nb\r\n"), "a\nb\n");
+        assert_eq!(normalize("a\rb"), "a\nb");
+        assert_eq!(normalize("a\r\n\r\nb"), "a\n\nb");
+    }
+
+    #[test]
+    fn lines_ignore_endings() {
+        for text in variants("12  \nab\n\ncd\n\n\n") {
+            let text = normalize(&text);
+            assert_eq!(lines(&text), vec!["12", "ab", "", "cd"], "{text:?}");
+        }
+        assert!(lines("").is_empty());
+        assert!(lines("\n\n").is_empty());
+    }
+
+    // Shaped like the day 5 input: ranges, a blank line, then IDs
+    #[test]
+    fn d5_sections() {
+        for text in variants("3-5\n10-14\n16-20\n12-18\n\n1\n5\n8\n11\n17\n32\n") {
+            let text = normalize(&text);
+            assert_eq!(
+                sections(&text),
+                vec![
+                    vec!["3-5", "10-14", "16-20", "12-18"],
+                    vec!["1", "5", "8", "11", "17", "32"],
+                ],
+                "{text:?}"
+            );
+        }
+        // several blank lines still make one split
+        assert_eq!(sections("a\n\n\n\nb"), vec![vec!["a"], vec!["b"]]);
+    }
+
+    // Shaped like the day 6 worksheet, where editors strip the trailing spaces that keep the
+    // columns aligned
+    #[test]
+    fn d6_padding() {
+        for text in variants("123 328  51 64\n 45 64  387 23\n  6 98  215 314\n*   +   *   +\n") {
+            let text = normalize(&text);
+            let padded = padded_lines(&text);
+            assert_eq!(
+                padded,
+                vec![
+                    "123 328  51 64 ",
+                    " 45 64  387 23 ",
+                    "  6 98  215 314",
+                    "*   +   *   +  ",
+                ],
+                "{text:?}"
+            );
+        }
+        assert!(padded_lines("").is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+pub mod big;
 pub mod d1;
 pub mod d2;
 pub mod d3;
@@ -9,6 +10,8 @@ pub mod d8;
 pub mod d9;
 pub mod d_10;
 pub mod d_11;
+pub mod input;
+pub mod rng;
 
 fn main() {
     // d1::solve();